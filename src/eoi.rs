@@ -0,0 +1,362 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::io::{
+    IoApic, IoApicEoi, IoApic64BitRegisterIndex, ReadableIoApicRegister, WritableIoApicRegister, RedirectionEntryRegister,
+    RedirectionEntryFlags, Vector, VersionFlags as IoApicVersionFlags,
+    VersionRegister as IoApicVersionRegister,
+};
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, WritableLocalApicRegister, Eoi, EoiRegister, InterruptVector,
+    SivrFlags, SpuriousInterruptVectorRegister, TriggerModeRegister, VersionRegister,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EoiError {
+    SuppressionUnsupported,
+    TooManyIoApics,
+    UnknownIoApic,
+    InvalidVector(Vector),
+    UnknownVector(Vector),
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for EoiError {
+    fn from(error: RegisterError) -> Self {
+        EoiError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EoiMode {
+    Broadcast,
+    Directed,
+}
+
+#[derive(Copy, Clone)]
+struct EoiTarget<'a> {
+    apic: &'a dyn IoApic,
+    eoi: Option<&'a dyn IoApicEoi>,
+    version: IoApicVersionFlags,
+}
+
+fn same_ioapic(a: &dyn IoApic, b: &dyn IoApic) -> bool {
+    a as *const dyn IoApic as *const () == b as *const dyn IoApic as *const ()
+}
+
+#[derive(Copy, Clone)]
+struct EoiPin {
+    ioapic: usize,
+    pin: u32,
+}
+
+pub struct EoiController<'a, const N: usize> {
+    local: &'a dyn LocalApic,
+    ioapics: [Option<EoiTarget<'a>>; N],
+    pins: [Option<EoiPin>; 256],
+    mode: EoiMode,
+}
+
+impl<'a, const N: usize> EoiController<'a, N> {
+    pub unsafe fn new(local: &'a dyn LocalApic) -> Result<Self, EoiError> {
+        let mode = if SpuriousInterruptVectorRegister.read(local)?.is_eoi_broadcast_supressed() {
+            EoiMode::Directed
+        } else {
+            EoiMode::Broadcast
+        };
+
+        Ok(EoiController { local, ioapics: [None; N], pins: [None; 256], mode })
+    }
+
+    pub fn mode(&self) -> EoiMode {
+        self.mode
+    }
+
+    /// Adds an ioapic to receive directed eois. The eoi register is only used
+    /// when it is given and the ioapic version reports one.
+    pub unsafe fn add_ioapic(&mut self, apic: &'a dyn IoApic, eoi: Option<&'a dyn IoApicEoi>) -> Result<(), EoiError> {
+        let version = IoApicVersionRegister.read(apic)?;
+        let slot = self.ioapics.iter_mut().find(|slot| slot.is_none()).ok_or(EoiError::TooManyIoApics)?;
+
+        *slot = Some(EoiTarget { apic, eoi, version });
+        Ok(())
+    }
+
+    /// Records that `vector` is routed through `pin` of an ioapic already given to
+    /// `add_ioapic`, replacing any earlier pin for the vector. Directed eois only
+    /// reach registered pins.
+    pub fn register_pin(&mut self, apic: &'a dyn IoApic, pin: u32, vector: Vector) -> Result<(), EoiError> {
+        let (ioapic, target) = self.ioapics.iter().enumerate()
+            .filter_map(|(index, target)| target.map(|target| (index, target)))
+            .find(|(_, target)| same_ioapic(target.apic, apic))
+            .ok_or(EoiError::UnknownIoApic)?;
+
+        IoApic64BitRegisterIndex::redirection_entry(pin, target.version)
+            .map_err(|_| RegisterError::InvalidRedirectionEntry(pin))?;
+
+        let slot = self.pins.get_mut(vector.0 as usize).ok_or(EoiError::InvalidVector(vector))?;
+        *slot = Some(EoiPin { ioapic, pin });
+        Ok(())
+    }
+
+    pub unsafe fn set_mode(&mut self, mode: EoiMode) -> Result<(), EoiError> {
+        if mode == EoiMode::Directed && !VersionRegister.read(self.local)?.can_suppress_eoi() {
+            return Err(EoiError::SuppressionUnsupported);
+        }
+
+        let sivr = SpuriousInterruptVectorRegister.read(self.local)?;
+        let sivr = match mode {
+            EoiMode::Broadcast => sivr - SivrFlags::EOI_BROADCAST_SUPRESSION,
+            EoiMode::Directed => sivr | SivrFlags::EOI_BROADCAST_SUPRESSION,
+        };
+
+        SpuriousInterruptVectorRegister.write(self.local, sivr)?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// In directed mode a level triggered vector is passed on to the ioapic pin
+    /// registered for it, and one without a registered pin is reported as
+    /// `EoiError::UnknownVector` once the local eoi has been written.
+    pub unsafe fn end_of_interrupt(&self, vector: InterruptVector) -> Result<(), EoiError> {
+        let level = TriggerModeRegister.read(self.local)?.contains(vector);
+        EoiRegister.write(self.local, Eoi(0))?;
+
+        if self.mode == EoiMode::Directed && level {
+            self.directed_eoi(Vector(vector.0))?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn directed_eoi(&self, vector: Vector) -> Result<(), EoiError> {
+        let owner = self.pins.get(vector.0 as usize).copied().flatten().ok_or(EoiError::UnknownVector(vector))?;
+        let target = self.ioapics[owner.ioapic].ok_or(EoiError::UnknownIoApic)?;
+
+        if let Some(eoi) = target.eoi.filter(|_| target.version.has_eoi_register()) {
+            // the eoi register clears remote irr on every pin with this vector
+            eoi.write_eoi(vector);
+            return Ok(());
+        }
+
+        let register = RedirectionEntryRegister(owner.pin);
+        let entry = register.read(target.apic)?;
+        if entry.vector() != vector || !entry.contains(RedirectionEntryFlags::REMOTE_IRR) {
+            return Ok(());
+        }
+
+        // without an eoi register, flipping the pin to masked edge clears remote irr
+        let edge = (entry | RedirectionEntryFlags::MASK) - RedirectionEntryFlags::TRIGGER_MODE;
+        register.write(target.apic, edge)?;
+        register.write(target.apic, entry)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::io::{EmulatedIoApic, IoApic32BitRegisterIndex, Polarity};
+    use crate::local::{EmulatedLocalApic, LvtTriggerMode};
+
+    struct CountingEoi<'a> {
+        ioapic: &'a EmulatedIoApic,
+        writes: Cell<usize>,
+    }
+
+    impl<'a> IoApicEoi for CountingEoi<'a> {
+        unsafe fn write_eoi(&self, vector: Vector) {
+            self.writes.set(self.writes.get() + 1);
+            self.ioapic.write_eoi(vector);
+        }
+    }
+
+    fn level_entry(vector: u32) -> RedirectionEntryFlags {
+        RedirectionEntryFlags::from_bits_truncate(vector as u64)
+            | RedirectionEntryFlags::TRIGGER_MODE
+            | RedirectionEntryFlags::from(Polarity::ActiveLow)
+    }
+
+    fn deliver(local: &EmulatedLocalApic, ioapic: &EmulatedIoApic, pin: usize) -> InterruptVector {
+        let message = ioapic.assert_pin(pin).expect("message");
+        local.request(InterruptVector(message.vector.0), LvtTriggerMode::Level);
+        local.accept().expect("accepted")
+    }
+
+    #[test]
+    pub fn test_broadcast_mode_skips_ioapic() {
+        let local = EmulatedLocalApic::new(0);
+        let ioapic = EmulatedIoApic::with_version(0, 24, 0x20);
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x1ff)).unwrap();
+            RedirectionEntryRegister(4).write(&ioapic, level_entry(0x44)).unwrap();
+
+            let mut controller = EoiController::<2>::new(&local).unwrap();
+            controller.add_ioapic(&ioapic, Some(&ioapic)).unwrap();
+            assert_eq!(controller.mode(), EoiMode::Broadcast);
+
+            let vector = deliver(&local, &ioapic, 4);
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(local.in_service().is_empty());
+            assert!(ioapic.entry(4).contains(RedirectionEntryFlags::REMOTE_IRR));
+        }
+    }
+
+    #[test]
+    pub fn test_directed_eoi_register() {
+        let local = EmulatedLocalApic::new(0);
+        let ioapic = EmulatedIoApic::with_version(0, 24, 0x20);
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x1ff)).unwrap();
+            RedirectionEntryRegister(4).write(&ioapic, level_entry(0x44)).unwrap();
+
+            let mut controller = EoiController::<2>::new(&local).unwrap();
+            controller.add_ioapic(&ioapic, Some(&ioapic)).unwrap();
+            controller.register_pin(&ioapic, 4, Vector(0x44)).unwrap();
+            controller.set_mode(EoiMode::Directed).unwrap();
+            assert!(SpuriousInterruptVectorRegister.read(&local).unwrap().is_eoi_broadcast_supressed());
+
+            let vector = deliver(&local, &ioapic, 4);
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(local.in_service().is_empty());
+            assert!(!ioapic.entry(4).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert_eq!(ioapic.illegal_accesses(), 0);
+
+            // the line is still asserted so the shared irq fires again
+            assert!(ioapic.service(4).is_some());
+        }
+    }
+
+    #[test]
+    pub fn test_directed_eoi_without_register() {
+        let local = EmulatedLocalApic::new(0);
+        let ioapic = EmulatedIoApic::new(0, 24);
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x11ff)).unwrap();
+            RedirectionEntryRegister(9).write(&ioapic, level_entry(0x49)).unwrap();
+            RedirectionEntryRegister(10).write(&ioapic, level_entry(0x3a)).unwrap();
+
+            let mut controller = EoiController::<1>::new(&local).unwrap();
+            controller.add_ioapic(&ioapic, None).unwrap();
+            controller.register_pin(&ioapic, 9, Vector(0x49)).unwrap();
+            controller.register_pin(&ioapic, 10, Vector(0x3a)).unwrap();
+            assert_eq!(controller.mode(), EoiMode::Directed);
+
+            deliver(&local, &ioapic, 10);
+            let vector = deliver(&local, &ioapic, 9);
+            assert_eq!(vector, InterruptVector(0x49));
+            controller.end_of_interrupt(vector).unwrap();
+
+            assert!(!ioapic.entry(9).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert!(ioapic.entry(10).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert_eq!(RedirectionEntryRegister(9).read(&ioapic).unwrap() - RedirectionEntryFlags::DELIVERY_STATUS, level_entry(0x49));
+            assert_eq!(ioapic.illegal_accesses(), 0);
+            assert!(controller.add_ioapic(&ioapic, None).is_err());
+        }
+    }
+
+    #[test]
+    pub fn test_directed_eoi_only_reaches_owner() {
+        let local = EmulatedLocalApic::new(0);
+        let legacy = EmulatedIoApic::new(0, 24);
+        let modern = EmulatedIoApic::with_version(1, 24, 0x20);
+        let eoi = CountingEoi { ioapic: &modern, writes: Cell::new(0) };
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x11ff)).unwrap();
+            RedirectionEntryRegister(9).write(&legacy, level_entry(0x49)).unwrap();
+            RedirectionEntryRegister(4).write(&modern, level_entry(0x44)).unwrap();
+
+            let mut controller = EoiController::<2>::new(&local).unwrap();
+            controller.add_ioapic(&legacy, None).unwrap();
+            controller.add_ioapic(&modern, Some(&eoi)).unwrap();
+            controller.register_pin(&legacy, 9, Vector(0x49)).unwrap();
+            controller.register_pin(&modern, 4, Vector(0x44)).unwrap();
+
+            let vector = deliver(&local, &legacy, 9);
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(!legacy.entry(9).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert_eq!(eoi.writes.get(), 0);
+
+            let vector = deliver(&local, &modern, 4);
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(!modern.entry(4).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert_eq!(eoi.writes.get(), 1);
+            assert_eq!(legacy.illegal_accesses() + modern.illegal_accesses(), 0);
+        }
+    }
+
+    struct CountingIoApic<'a> {
+        ioapic: &'a EmulatedIoApic,
+        entry_reads: Cell<usize>,
+    }
+
+    impl<'a> IoApic for CountingIoApic<'a> {
+        unsafe fn read_reg_32(&self, index: IoApic32BitRegisterIndex) -> u32 {
+            self.ioapic.read_reg_32(index)
+        }
+
+        unsafe fn write_reg_32(&self, index: IoApic32BitRegisterIndex, value: u32) {
+            self.ioapic.write_reg_32(index, value)
+        }
+
+        unsafe fn read_reg_64(&self, index: IoApic64BitRegisterIndex) -> u64 {
+            self.entry_reads.set(self.entry_reads.get() + 1);
+            self.ioapic.read_reg_64(index)
+        }
+
+        unsafe fn write_reg_64(&self, index: IoApic64BitRegisterIndex, value: u64) {
+            self.ioapic.write_reg_64(index, value)
+        }
+    }
+
+    #[test]
+    pub fn test_directed_eoi_reads_only_owning_pin() {
+        let local = EmulatedLocalApic::new(0);
+        let ioapic = EmulatedIoApic::new(0, 24);
+        let counting = CountingIoApic { ioapic: &ioapic, entry_reads: Cell::new(0) };
+        let stranger = EmulatedIoApic::new(1, 24);
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x11ff)).unwrap();
+            RedirectionEntryRegister(17).write(&ioapic, level_entry(0x51)).unwrap();
+
+            let mut controller = EoiController::<1>::new(&local).unwrap();
+            controller.add_ioapic(&counting, None).unwrap();
+            assert_eq!(controller.register_pin(&stranger, 3, Vector(0x43)), Err(EoiError::UnknownIoApic));
+            assert_eq!(controller.register_pin(&counting, 24, Vector(0x43)),
+                Err(EoiError::Register(RegisterError::InvalidRedirectionEntry(24))));
+            assert_eq!(controller.register_pin(&counting, 3, Vector(0x100)), Err(EoiError::InvalidVector(Vector(0x100))));
+            controller.register_pin(&counting, 17, Vector(0x51)).unwrap();
+
+            let vector = deliver(&local, &ioapic, 17);
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(!ioapic.entry(17).contains(RedirectionEntryFlags::REMOTE_IRR));
+            assert_eq!(counting.entry_reads.get(), 1);
+
+            local.request(InterruptVector(0x62), LvtTriggerMode::Level);
+            let vector = local.accept().unwrap();
+            assert_eq!(controller.end_of_interrupt(vector), Err(EoiError::UnknownVector(Vector(0x62))));
+            assert!(local.in_service().is_empty());
+        }
+    }
+
+    #[test]
+    pub fn test_edge_vectors_need_no_directed_eoi() {
+        let local = EmulatedLocalApic::new(0);
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&local, SivrFlags::from_bits_truncate(0x11ff)).unwrap();
+            let controller = EoiController::<1>::new(&local).unwrap();
+
+            local.request(InterruptVector(0x30), LvtTriggerMode::Edge);
+            let vector = local.accept().unwrap();
+            controller.end_of_interrupt(vector).unwrap();
+            assert!(local.in_service().is_empty());
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use crate::local::LocalApicRegisterIndex;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RegisterError {
+    UndefinedBits(u64),
+    ReservedValue { field: &'static str, value: u32 },
+    Unsupported(LocalApicRegisterIndex),
+    InvalidRedirectionEntry(u32),
+}
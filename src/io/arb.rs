@@ -1,4 +1,5 @@
-use crate::io::{IoApic, IoApicRegister, IoApic32BitRegisterIndex};
+use crate::error::RegisterError;
+use crate::io::{IoApic, IoApicRegister, ReadableIoApicRegister, IoApic32BitRegisterIndex};
 
 bitflags! {
     pub struct ArbitrationIdFlags: u32 {
@@ -25,12 +26,10 @@ pub struct ArbitrationId(pub u32);
 pub struct ArbitrationIdRegister;
 impl IoApicRegister for ArbitrationIdRegister {
     type Value = ArbitrationId;
+}
 
-    unsafe fn read(&self, apic: &dyn IoApic) -> Self::Value {
-        ArbitrationIdFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::ArbitrationId)).id()
-    }
-
-    unsafe fn write(&self, _apic: &dyn IoApic, _value: Self::Value) {
-        panic!("read only register");
+impl ReadableIoApicRegister for ArbitrationIdRegister {
+    unsafe fn read(&self, apic: &dyn IoApic) -> Result<Self::Value, RegisterError> {
+        Ok(ArbitrationIdFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::ArbitrationId)).id())
     }
 }
\ No newline at end of file
@@ -0,0 +1,327 @@
+use core::cell::RefCell;
+use crate::io::{
+    IoApic, IoApicEoi, IoApic32BitRegisterIndex, IoApic64BitRegisterIndex, RedirectionEntryFlags, Vector,
+    DeliveryMode, Destination, TriggerMode, Mask, VersionFlags,
+};
+
+const MAX_ENTRIES: usize = IoApic64BitRegisterIndex::MAX_PIN as usize + 1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IoApicMessage {
+    pub vector: Vector,
+    pub delivery_mode: DeliveryMode,
+    pub destination: Destination,
+    pub trigger_mode: TriggerMode,
+}
+
+impl From<RedirectionEntryFlags> for IoApicMessage {
+    fn from(entry: RedirectionEntryFlags) -> Self {
+        IoApicMessage {
+            vector: entry.vector(),
+            delivery_mode: entry.delivery_mode(),
+            destination: entry.destination(),
+            trigger_mode: entry.trigger_mode(),
+        }
+    }
+}
+
+struct State {
+    id: u32,
+    arbitration_id: u32,
+    version: u32,
+    entries: [RedirectionEntryFlags; MAX_ENTRIES],
+    asserted: [bool; MAX_ENTRIES],
+    edge_pending: [bool; MAX_ENTRIES],
+    illegal_accesses: usize,
+}
+
+pub struct EmulatedIoApic {
+    entry_count: usize,
+    state: RefCell<State>,
+}
+
+impl EmulatedIoApic {
+    pub fn new(id: u8, entry_count: usize) -> Self {
+        Self::with_version(id, entry_count, 0x11)
+    }
+
+    pub fn with_version(id: u8, entry_count: usize, version: u8) -> Self {
+        assert!(entry_count > 0 && entry_count <= MAX_ENTRIES, "invalid redirection entry count");
+
+        EmulatedIoApic {
+            entry_count,
+            state: RefCell::new(State {
+                id: (id as u32 & 0xf) << 24,
+                arbitration_id: (id as u32 & 0xf) << 24,
+                version: (((entry_count - 1) as u32) << 16) | version as u32,
+                entries: [RedirectionEntryFlags::MASK; MAX_ENTRIES],
+                asserted: [false; MAX_ENTRIES],
+                edge_pending: [false; MAX_ENTRIES],
+                illegal_accesses: 0,
+            }),
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn entry(&self, pin: usize) -> RedirectionEntryFlags {
+        self.state.borrow().entries[pin]
+    }
+
+    pub fn illegal_accesses(&self) -> usize {
+        self.state.borrow().illegal_accesses
+    }
+
+    pub fn is_asserted(&self, pin: usize) -> bool {
+        self.state.borrow().asserted[pin]
+    }
+
+    pub fn assert_pin(&self, pin: usize) -> Option<IoApicMessage> {
+        assert!(pin < self.entry_count, "invalid pin");
+
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.asserted[pin] {
+                state.edge_pending[pin] = true;
+            }
+            state.asserted[pin] = true;
+        }
+
+        self.service(pin)
+    }
+
+    pub fn deassert_pin(&self, pin: usize) {
+        assert!(pin < self.entry_count, "invalid pin");
+
+        let mut state = self.state.borrow_mut();
+        state.asserted[pin] = false;
+        if state.entries[pin].trigger_mode() == TriggerMode::Level {
+            state.entries[pin].remove(RedirectionEntryFlags::DELIVERY_STATUS);
+        }
+    }
+
+    pub fn service(&self, pin: usize) -> Option<IoApicMessage> {
+        let mut state = self.state.borrow_mut();
+        let entry = state.entries[pin];
+
+        let level = entry.trigger_mode() == TriggerMode::Level;
+        if (level && !state.asserted[pin]) || (!level && !state.edge_pending[pin]) {
+            return None;
+        }
+
+        if !level && entry.mask() == Mask::Masked {
+            // masked edges are dropped rather than held pending
+            state.edge_pending[pin] = false;
+            return None;
+        }
+
+        if entry.mask() == Mask::Masked || (level && entry.contains(RedirectionEntryFlags::REMOTE_IRR)) {
+            state.entries[pin].insert(RedirectionEntryFlags::DELIVERY_STATUS);
+            return None;
+        }
+
+        state.entries[pin].remove(RedirectionEntryFlags::DELIVERY_STATUS);
+        if level {
+            state.entries[pin].insert(RedirectionEntryFlags::REMOTE_IRR);
+        } else {
+            // edge triggered pins only deliver once per rising edge
+            state.edge_pending[pin] = false;
+        }
+
+        Some(IoApicMessage::from(entry))
+    }
+
+    pub fn end_of_interrupt(&self, vector: Vector) {
+        let mut state = self.state.borrow_mut();
+        for entry in state.entries[..self.entry_count].iter_mut() {
+            if entry.trigger_mode() == TriggerMode::Level && entry.vector() == vector {
+                entry.remove(RedirectionEntryFlags::REMOTE_IRR);
+            }
+        }
+    }
+}
+
+impl IoApic for EmulatedIoApic {
+    unsafe fn read_reg_32(&self, index: IoApic32BitRegisterIndex) -> u32 {
+        let state = self.state.borrow();
+        match index {
+            IoApic32BitRegisterIndex::Id => state.id,
+            IoApic32BitRegisterIndex::Version => state.version,
+            IoApic32BitRegisterIndex::ArbitrationId => state.arbitration_id,
+        }
+    }
+
+    unsafe fn write_reg_32(&self, index: IoApic32BitRegisterIndex, value: u32) {
+        let mut state = self.state.borrow_mut();
+        match index {
+            IoApic32BitRegisterIndex::Id => state.id = value & 0x0f00_0000,
+            _ => state.illegal_accesses += 1,
+        }
+    }
+
+    unsafe fn read_reg_64(&self, index: IoApic64BitRegisterIndex) -> u64 {
+        let mut state = self.state.borrow_mut();
+        match index {
+            IoApic64BitRegisterIndex::RedirectionEntry(pin) if (pin as usize) < self.entry_count => {
+                state.entries[pin as usize].bits()
+            }
+            _ => {
+                state.illegal_accesses += 1;
+                0
+            }
+        }
+    }
+
+    unsafe fn write_reg_64(&self, index: IoApic64BitRegisterIndex, value: u64) {
+        let mut state = self.state.borrow_mut();
+        match index {
+            IoApic64BitRegisterIndex::RedirectionEntry(pin) if (pin as usize) < self.entry_count => {
+                let read_only = RedirectionEntryFlags::DELIVERY_STATUS | RedirectionEntryFlags::REMOTE_IRR;
+                let entry = &mut state.entries[pin as usize];
+                *entry = (*entry & read_only) | (RedirectionEntryFlags::from_bits_truncate(value) - read_only);
+
+                // switching a pin to edge triggered drops remote irr
+                if entry.trigger_mode() == TriggerMode::Edge {
+                    entry.remove(RedirectionEntryFlags::REMOTE_IRR);
+                }
+            }
+            _ => state.illegal_accesses += 1,
+        }
+    }
+}
+
+impl IoApicEoi for EmulatedIoApic {
+    unsafe fn write_eoi(&self, vector: Vector) {
+        if VersionFlags::from_bits_truncate(self.state.borrow().version).has_eoi_register() {
+            self.end_of_interrupt(vector);
+        } else {
+            self.state.borrow_mut().illegal_accesses += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::*;
+
+    fn level_entry(vector: u64) -> RedirectionEntryFlags {
+        RedirectionEntryFlags::from_bits_truncate(vector | 0x0300_0000_0000_0000) | RedirectionEntryFlags::TRIGGER_MODE
+    }
+
+    #[test]
+    pub fn test_version_reflects_entries() {
+        let ioapic = EmulatedIoApic::new(2, 24);
+
+        unsafe {
+            assert_eq!(VersionRegister.read(&ioapic).unwrap().max_redirect_entry(), 23);
+            assert_eq!(VersionRegister.read(&ioapic).unwrap().version(), Version(0x11));
+            assert_eq!(IdRegister.read(&ioapic).unwrap(), ApicId(2));
+
+            IdRegister.write(&ioapic, ApicId(5)).unwrap();
+            assert_eq!(IdRegister.read(&ioapic).unwrap(), ApicId(5));
+            assert_eq!(ArbitrationIdRegister.read(&ioapic).unwrap(), ArbitrationId(2));
+
+            ioapic.write_reg_32(IoApic32BitRegisterIndex::Version, 0);
+            assert_eq!(VersionRegister.read(&ioapic).unwrap().max_redirect_entry(), 23);
+            assert_eq!(ioapic.illegal_accesses(), 1);
+        }
+    }
+
+    #[test]
+    pub fn test_masked_pin_is_not_delivered() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+
+        unsafe {
+            RedirectionEntryRegister(1).write(&ioapic, level_entry(0x41) | RedirectionEntryFlags::MASK).unwrap();
+        }
+
+        assert_eq!(ioapic.assert_pin(1), None);
+        assert_eq!(ioapic.entry(1).delivery_status(), DeliveryStatus::SendPending);
+
+        unsafe {
+            RedirectionEntryRegister(1).write(&ioapic, level_entry(0x41)).unwrap();
+        }
+        assert_eq!(ioapic.service(1).map(|message| message.vector), Some(Vector(0x41)));
+
+        assert_eq!(ioapic.assert_pin(2), None);
+        assert_eq!(ioapic.entry(2).delivery_status(), DeliveryStatus::Idle);
+    }
+
+    #[test]
+    pub fn test_edge_triggered_pin() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+        unsafe {
+            RedirectionEntryRegister(4).write(&ioapic, RedirectionEntryFlags::from_bits_truncate(0x0100_0000_0000_0031)).unwrap();
+        }
+
+        let message = ioapic.assert_pin(4).expect("message");
+        assert_eq!(message.vector, Vector(0x31));
+        assert_eq!(message.destination, Destination::Physical(1));
+        assert_eq!(message.delivery_mode, DeliveryMode::Fixed);
+        assert_eq!(message.trigger_mode, TriggerMode::Edge);
+
+        // held high without a new edge
+        assert_eq!(ioapic.assert_pin(4), None);
+        ioapic.deassert_pin(4);
+        assert!(ioapic.assert_pin(4).is_some());
+    }
+
+    #[test]
+    pub fn test_level_triggered_remote_irr() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+        unsafe {
+            RedirectionEntryRegister(9).write(&ioapic, level_entry(0x49)).unwrap();
+        }
+
+        assert!(ioapic.assert_pin(9).is_some());
+        assert!(ioapic.entry(9).contains(RedirectionEntryFlags::REMOTE_IRR));
+
+        // remote irr blocks redelivery until eoi
+        assert_eq!(ioapic.assert_pin(9), None);
+        assert_eq!(ioapic.entry(9).delivery_status(), DeliveryStatus::SendPending);
+
+        // software can't clear remote irr
+        unsafe {
+            RedirectionEntryRegister(9).write(&ioapic, level_entry(0x49)).unwrap();
+            assert!(RedirectionEntryRegister(9).read(&ioapic).unwrap().contains(RedirectionEntryFlags::REMOTE_IRR));
+        }
+
+        ioapic.end_of_interrupt(Vector(0x49));
+        assert!(!ioapic.entry(9).contains(RedirectionEntryFlags::REMOTE_IRR));
+        assert!(ioapic.service(9).is_some());
+
+        ioapic.end_of_interrupt(Vector(0x49));
+        ioapic.deassert_pin(9);
+        assert_eq!(ioapic.service(9), None);
+    }
+
+    #[test]
+    pub fn test_eoi_register() {
+        let ioapic = EmulatedIoApic::with_version(0, 24, 0x20);
+        unsafe {
+            RedirectionEntryRegister(3).write(&ioapic, level_entry(0x43)).unwrap();
+            assert!(VersionRegister.read(&ioapic).unwrap().has_eoi_register());
+        }
+
+        assert!(ioapic.assert_pin(3).is_some());
+        unsafe {
+            ioapic.write_eoi(Vector(0x43));
+        }
+        assert!(!ioapic.entry(3).contains(RedirectionEntryFlags::REMOTE_IRR));
+        assert_eq!(ioapic.illegal_accesses(), 0);
+    }
+
+    #[test]
+    pub fn test_all_redirection_entries() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+
+        unsafe {
+            assert_eq!(RedirectionEntryRegister::all(&ioapic).unwrap().count(), 24);
+            assert!(RedirectionEntryRegister::all(&ioapic).unwrap().all(|register| register.read(&ioapic).unwrap().mask() == Mask::Masked));
+            assert!(RedirectionEntryRegister::new(24, VersionRegister.read(&ioapic).unwrap()).is_err());
+        }
+    }
+}
@@ -0,0 +1,5 @@
+use crate::io::Vector;
+
+pub trait IoApicEoi {
+    unsafe fn write_eoi(&self, vector: Vector);
+}
@@ -0,0 +1,291 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::madt::MpsIntiFlags;
+use crate::io::{
+    IoApic, IoApic64BitRegisterIndex, ReadableIoApicRegister, WritableIoApicRegister, VersionRegister, RedirectionEntryRegister,
+    RedirectionEntryFlags, RedirectionEntry, PhysicalDestinationWidth, Polarity, TriggerMode, Vector, Destination,
+};
+
+pub const ISA_IRQS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GsiError {
+    UnknownGsi(u32),
+    UnknownIsaIrq(u8),
+    TooManyIoApics,
+    OverlappingGsiRange(u32),
+    InvalidEntry(&'static str),
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for GsiError {
+    fn from(error: RegisterError) -> Self {
+        GsiError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IsaIrq {
+    pub gsi: u32,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+}
+
+impl IsaIrq {
+    pub fn identity(irq: u8) -> Self {
+        IsaIrq {
+            gsi: irq as u32,
+            polarity: Polarity::ActiveHigh,
+            trigger_mode: TriggerMode::Edge,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct RoutedIoApic<'a> {
+    apic: &'a dyn IoApic,
+    gsi_base: u32,
+    entries: u32,
+}
+
+impl<'a> RoutedIoApic<'a> {
+    fn contains(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi - self.gsi_base < self.entries
+    }
+}
+
+pub struct GsiRouter<'a, const N: usize> {
+    ioapics: [Option<RoutedIoApic<'a>>; N],
+    isa: [IsaIrq; ISA_IRQS],
+    width: PhysicalDestinationWidth,
+}
+
+impl<'a, const N: usize> GsiRouter<'a, N> {
+    pub fn new(width: PhysicalDestinationWidth) -> Self {
+        let mut isa = [IsaIrq::identity(0); ISA_IRQS];
+        for (irq, entry) in isa.iter_mut().enumerate() {
+            *entry = IsaIrq::identity(irq as u8);
+        }
+
+        GsiRouter {
+            ioapics: [None; N],
+            isa,
+            width,
+        }
+    }
+
+    pub unsafe fn add_ioapic(&mut self, apic: &'a dyn IoApic, gsi_base: u32) -> Result<(), GsiError> {
+        // the register select cannot reach pins past MAX_PIN, whatever the version register claims
+        let max_redirect_entry = VersionRegister.read(apic)?.max_redirect_entry();
+        let entries = max_redirect_entry.min(IoApic64BitRegisterIndex::MAX_PIN) + 1;
+        let routed = RoutedIoApic { apic, gsi_base, entries };
+
+        for existing in self.ioapics.iter().flatten() {
+            if existing.contains(gsi_base) || routed.contains(existing.gsi_base) {
+                return Err(GsiError::OverlappingGsiRange(gsi_base));
+            }
+        }
+
+        let slot = self.ioapics.iter_mut().find(|slot| slot.is_none()).ok_or(GsiError::TooManyIoApics)?;
+        *slot = Some(routed);
+        Ok(())
+    }
+
+    pub fn add_override(&mut self, source: u8, gsi: u32, flags: MpsIntiFlags) -> Result<(), GsiError> {
+        let entry = self.isa.get_mut(source as usize).ok_or(GsiError::UnknownIsaIrq(source))?;
+
+        // isa defaults apply when the override conforms to the bus
+        *entry = IsaIrq {
+            gsi,
+            polarity: flags.polarity_or(Polarity::ActiveHigh),
+            trigger_mode: flags.trigger_mode_or(TriggerMode::Edge),
+        };
+        Ok(())
+    }
+
+    pub fn resolve_isa(&self, irq: u8) -> Result<IsaIrq, GsiError> {
+        self.isa.get(irq as usize).copied().ok_or(GsiError::UnknownIsaIrq(irq))
+    }
+
+    pub fn contains(&self, gsi: u32) -> bool {
+        self.locate(gsi).is_ok()
+    }
+
+    pub unsafe fn route(&self, gsi: u32, entry: RedirectionEntry) -> Result<(), GsiError> {
+        let flags = entry.to_flags(self.width).map_err(GsiError::InvalidEntry)?;
+        let (apic, register) = self.locate(gsi)?;
+
+        Ok(register.write(apic, flags)?)
+    }
+
+    pub unsafe fn route_isa(&self, irq: u8, vector: Vector, destination: Destination) -> Result<u32, GsiError> {
+        let isa = self.resolve_isa(irq)?;
+        let entry = RedirectionEntry::new(vector, destination)
+            .with_polarity(isa.polarity)
+            .with_trigger_mode(isa.trigger_mode);
+
+        self.route(isa.gsi, entry)?;
+        Ok(isa.gsi)
+    }
+
+    pub unsafe fn unroute(&self, gsi: u32) -> Result<(), GsiError> {
+        let (apic, register) = self.locate(gsi)?;
+
+        Ok(register.write(apic, RedirectionEntryFlags::MASK)?)
+    }
+
+    pub unsafe fn mask(&self, gsi: u32) -> Result<(), GsiError> {
+        let (apic, register) = self.locate(gsi)?;
+        let value = register.read(apic)?;
+
+        Ok(register.write(apic, value | RedirectionEntryFlags::MASK)?)
+    }
+
+    pub unsafe fn unmask(&self, gsi: u32) -> Result<(), GsiError> {
+        let (apic, register) = self.locate(gsi)?;
+        let value = register.read(apic)?;
+
+        Ok(register.write(apic, value - RedirectionEntryFlags::MASK)?)
+    }
+
+    pub unsafe fn entry(&self, gsi: u32) -> Result<RedirectionEntryFlags, GsiError> {
+        let (apic, register) = self.locate(gsi)?;
+        Ok(register.read(apic)?)
+    }
+
+    fn locate(&self, gsi: u32) -> Result<(&'a dyn IoApic, RedirectionEntryRegister), GsiError> {
+        self.ioapics.iter().flatten()
+            .find(|ioapic| ioapic.contains(gsi))
+            .map(|ioapic| (ioapic.apic, RedirectionEntryRegister(gsi - ioapic.gsi_base)))
+            .ok_or(GsiError::UnknownGsi(gsi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{EmulatedIoApic, Mask};
+
+    #[test]
+    pub fn test_route_across_ioapics() {
+        let first = EmulatedIoApic::new(0, 24);
+        let second = EmulatedIoApic::new(1, 16);
+        let mut router: GsiRouter<2> = GsiRouter::new(PhysicalDestinationWidth::EightBit);
+
+        unsafe {
+            router.add_ioapic(&first, 0).expect("first");
+            router.add_ioapic(&second, 24).expect("second");
+            assert_eq!(router.add_ioapic(&first, 40), Err(GsiError::TooManyIoApics));
+
+            router.route(30, RedirectionEntry::new(Vector(0x50), Destination::Physical(2))).expect("route");
+        }
+
+        let entry = second.entry(6);
+        assert_eq!(entry.vector(), Vector(0x50));
+        assert_eq!(entry.mask(), Mask::NotMasked);
+        assert!(router.contains(39));
+        assert!(!router.contains(40));
+
+        unsafe {
+            assert_eq!(router.route(40, RedirectionEntry::new(Vector(0x50), Destination::Physical(2))),
+                Err(GsiError::UnknownGsi(40)));
+        }
+    }
+
+    #[test]
+    pub fn test_overlapping_ranges() {
+        let first = EmulatedIoApic::new(0, 24);
+        let second = EmulatedIoApic::new(1, 24);
+        let mut router: GsiRouter<4> = GsiRouter::new(PhysicalDestinationWidth::FourBit);
+
+        unsafe {
+            router.add_ioapic(&first, 16).expect("first");
+            assert_eq!(router.add_ioapic(&second, 0), Err(GsiError::OverlappingGsiRange(0)));
+            assert_eq!(router.add_ioapic(&second, 39), Err(GsiError::OverlappingGsiRange(39)));
+            assert!(router.add_ioapic(&second, 40).is_ok());
+        }
+    }
+
+    #[test]
+    pub fn test_isa_overrides() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+        let mut router: GsiRouter<1> = GsiRouter::new(PhysicalDestinationWidth::FourBit);
+
+        router.add_override(0, 2, MpsIntiFlags { polarity: None, trigger_mode: None }).expect("timer");
+        router.add_override(9, 9, MpsIntiFlags {
+            polarity: Some(Polarity::ActiveLow),
+            trigger_mode: Some(TriggerMode::Level),
+        }).expect("sci");
+        assert_eq!(router.add_override(16, 16, MpsIntiFlags { polarity: None, trigger_mode: None }),
+            Err(GsiError::UnknownIsaIrq(16)));
+
+        assert_eq!(router.resolve_isa(0).map(|isa| isa.gsi), Ok(2));
+        assert_eq!(router.resolve_isa(1), Ok(IsaIrq::identity(1)));
+
+        unsafe {
+            router.add_ioapic(&ioapic, 0).expect("ioapic");
+            assert_eq!(router.route_isa(0, Vector(0x20), Destination::Physical(0)), Ok(2));
+            assert_eq!(router.route_isa(9, Vector(0x29), Destination::Physical(0)), Ok(9));
+        }
+
+        assert_eq!(ioapic.entry(2).vector(), Vector(0x20));
+        assert_eq!(ioapic.entry(9).polarity(), Polarity::ActiveLow);
+        assert_eq!(ioapic.entry(9).trigger_mode(), TriggerMode::Level);
+    }
+
+    #[test]
+    pub fn test_mask_and_unroute() {
+        let ioapic = EmulatedIoApic::new(0, 24);
+        let mut router: GsiRouter<1> = GsiRouter::new(PhysicalDestinationWidth::FourBit);
+
+        unsafe {
+            router.add_ioapic(&ioapic, 0).expect("ioapic");
+            router.route(4, RedirectionEntry::new(Vector(0x34), Destination::Physical(1))).expect("route");
+
+            router.mask(4).expect("mask");
+            assert_eq!(router.entry(4).map(|entry| entry.mask()), Ok(Mask::Masked));
+            assert_eq!(router.entry(4).map(|entry| entry.vector()), Ok(Vector(0x34)));
+
+            router.unmask(4).expect("unmask");
+            assert_eq!(router.entry(4).map(|entry| entry.mask()), Ok(Mask::NotMasked));
+
+            router.unroute(4).expect("unroute");
+            assert_eq!(router.entry(4), Ok(RedirectionEntryFlags::MASK));
+
+            assert_eq!(router.mask(24), Err(GsiError::UnknownGsi(24)));
+            assert_eq!(router.route(5, RedirectionEntry::new(Vector(0x05), Destination::Physical(1))),
+                Err(GsiError::InvalidEntry("vector out of range")));
+        }
+    }
+
+    #[test]
+    pub fn test_entries_clamped_to_register_select() {
+        use crate::io::IoApic32BitRegisterIndex;
+
+        struct WideIoApic;
+
+        impl IoApic for WideIoApic {
+            unsafe fn read_reg_32(&self, _index: IoApic32BitRegisterIndex) -> u32 {
+                0x00ff_0020
+            }
+
+            unsafe fn write_reg_32(&self, _index: IoApic32BitRegisterIndex, _value: u32) {}
+
+            unsafe fn read_reg_64(&self, _index: IoApic64BitRegisterIndex) -> u64 {
+                RedirectionEntryFlags::MASK.bits()
+            }
+
+            unsafe fn write_reg_64(&self, _index: IoApic64BitRegisterIndex, _value: u64) {}
+        }
+
+        let ioapic = WideIoApic;
+        let mut router: GsiRouter<1> = GsiRouter::new(PhysicalDestinationWidth::EightBit);
+
+        unsafe {
+            router.add_ioapic(&ioapic, 0).expect("ioapic");
+        }
+        assert!(router.contains(0x77));
+        assert!(!router.contains(0x78));
+        assert!(!router.contains(0xff));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::io::{IoApic, IoApicRegister, IoApic32BitRegisterIndex};
+use crate::error::RegisterError;
+use crate::io::{IoApic, IoApicRegister, ReadableIoApicRegister, WritableIoApicRegister, IoApic32BitRegisterIndex};
 
 bitflags! {
     pub struct IdFlags: u32 {
@@ -25,12 +26,17 @@ pub struct ApicId(pub u32);
 pub struct IdRegister;
 impl IoApicRegister for IdRegister {
     type Value = ApicId;
+}
 
-    unsafe fn read(&self, apic: &dyn IoApic) -> Self::Value {
-        IdFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::Id)).id()
+impl ReadableIoApicRegister for IdRegister {
+    unsafe fn read(&self, apic: &dyn IoApic) -> Result<Self::Value, RegisterError> {
+        Ok(IdFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::Id)).id())
     }
+}
 
-    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value) {
+impl WritableIoApicRegister for IdRegister {
+    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value) -> Result<(), RegisterError> {
         apic.write_reg_32(IoApic32BitRegisterIndex::Id, IdFlags::from(value).bits());
+        Ok(())
     }
 }
\ No newline at end of file
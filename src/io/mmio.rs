@@ -0,0 +1,147 @@
+use core::ptr;
+use crate::io::{IoApic, IoApicEoi, IoApic32BitRegisterIndex, IoApic64BitRegisterIndex, RedirectionEntryFlags, Vector};
+
+pub struct MmioIoApic {
+    base: usize,
+}
+
+impl MmioIoApic {
+    pub const REGISTER_SELECT: usize = 0x00;
+    pub const WINDOW: usize = 0x10;
+    pub const EOI: usize = 0x40;
+
+    pub const unsafe fn new(base: usize) -> Self {
+        MmioIoApic { base }
+    }
+
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub unsafe fn read_indirect(&self, register: u32) -> u32 {
+        ptr::write_volatile((self.base + Self::REGISTER_SELECT) as *mut u32, register);
+        ptr::read_volatile((self.base + Self::WINDOW) as *const u32)
+    }
+
+    pub unsafe fn write_indirect(&self, register: u32, value: u32) {
+        ptr::write_volatile((self.base + Self::REGISTER_SELECT) as *mut u32, register);
+        ptr::write_volatile((self.base + Self::WINDOW) as *mut u32, value);
+    }
+}
+
+impl IoApic for MmioIoApic {
+    unsafe fn read_reg_32(&self, index: IoApic32BitRegisterIndex) -> u32 {
+        self.read_indirect(index.as_u32())
+    }
+
+    unsafe fn write_reg_32(&self, index: IoApic32BitRegisterIndex, value: u32) {
+        self.write_indirect(index.as_u32(), value);
+    }
+
+    unsafe fn read_reg_64(&self, index: IoApic64BitRegisterIndex) -> u64 {
+        // callers are expected to go through the checked redirection_entry path; in release
+        // builds a pin past the register select range has no registers and reads as zero
+        let register = index.low_register();
+        debug_assert!(register.is_ok(), "redirection entry selector out of range");
+        let register = match register {
+            Ok(register) => register,
+            Err(_) => return 0,
+        };
+
+        let low = self.read_indirect(register);
+        let high = self.read_indirect(register + 1);
+
+        ((high as u64) << 32) | low as u64
+    }
+
+    unsafe fn write_reg_64(&self, index: IoApic64BitRegisterIndex, value: u64) {
+        let register = index.low_register();
+        debug_assert!(register.is_ok(), "redirection entry selector out of range");
+        let register = match register {
+            Ok(register) => register,
+            Err(_) => return,
+        };
+
+        let low = value as u32;
+        let high = (value >> 32) as u32;
+
+        // the mask bit lives in the low dword, so only let it go last when unmasking
+        if RedirectionEntryFlags::from_bits_truncate(value).contains(RedirectionEntryFlags::MASK) {
+            self.write_indirect(register, low);
+            self.write_indirect(register + 1, high);
+        } else {
+            self.write_indirect(register + 1, high);
+            self.write_indirect(register, low);
+        }
+    }
+}
+
+impl IoApicEoi for MmioIoApic {
+    unsafe fn write_eoi(&self, vector: Vector) {
+        ptr::write_volatile((self.base + Self::EOI) as *mut u32, vector.0 & 0xff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_unmasking_writes_low_dword_last() {
+        let mut window = [0u32; 20];
+        let ioapic = unsafe { MmioIoApic::new(window.as_mut_ptr() as usize) };
+
+        unsafe {
+            ioapic.write_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(2), 0x0100_0000_0000_0031);
+        }
+        assert_eq!(window[0], 0x14);
+        assert_eq!(window[4], 0x31);
+    }
+
+    #[test]
+    pub fn test_masking_writes_high_dword_last() {
+        let mut window = [0u32; 20];
+        let ioapic = unsafe { MmioIoApic::new(window.as_mut_ptr() as usize) };
+
+        unsafe {
+            ioapic.write_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(2), 0x0100_0000_0001_0031);
+        }
+        assert_eq!(window[0], 0x15);
+        assert_eq!(window[4], 0x0100_0000);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "redirection entry selector out of range"))]
+    pub fn test_out_of_range_write_is_ignored() {
+        let mut window = [0u32; 20];
+        let ioapic = unsafe { MmioIoApic::new(window.as_mut_ptr() as usize) };
+
+        unsafe {
+            ioapic.write_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(u32::MAX), 0x31);
+        }
+        assert!(window.iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "redirection entry selector out of range"))]
+    pub fn test_out_of_range_read_is_zero() {
+        let mut window = [0u32; 20];
+        let ioapic = unsafe { MmioIoApic::new(window.as_mut_ptr() as usize) };
+
+        unsafe {
+            assert_eq!(ioapic.read_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(0x78)), 0);
+        }
+        assert!(window.iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    pub fn test_eoi() {
+        let mut window = [0u32; 20];
+        let ioapic = unsafe { MmioIoApic::new(window.as_mut_ptr() as usize) };
+
+        unsafe {
+            ioapic.write_eoi(Vector(0x41));
+        }
+        assert_eq!(window[16], 0x41);
+    }
+}
@@ -1,15 +1,29 @@
 pub mod arb;
+#[cfg(any(test, feature = "emulation"))]
+pub mod emulated;
+pub mod eoi;
+pub mod gsi;
 pub mod id;
+pub mod mmio;
 pub mod redirection;
 pub mod registers;
 pub mod version;
 
 pub use arb::*;
+#[cfg(any(test, feature = "emulation"))]
+pub use emulated::*;
+pub use eoi::*;
+pub use gsi::*;
 pub use id::*;
+pub use mmio::*;
 pub use redirection::*;
 pub use registers::*;
 pub use version::*;
 
+/// # Safety
+///
+/// Implementations must refer to a live, correctly mapped ioapic. Every
+/// unsafe register accessor in this crate relies on that and nothing else.
 pub trait IoApic {
     unsafe fn read_reg_32(&self, index: IoApic32BitRegisterIndex) -> u32;
     unsafe fn write_reg_32(&self, index: IoApic32BitRegisterIndex, value: u32);
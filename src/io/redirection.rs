@@ -1,20 +1,21 @@
 use core::result::Result;
 use core::convert::TryFrom;
-use crate::io::{IoApic, IoApicRegister, IoApic64BitRegisterIndex};
+use crate::error::RegisterError;
+use crate::io::{IoApic, IoApicRegister, ReadableIoApicRegister, WritableIoApicRegister, IoApic64BitRegisterIndex, VersionFlags, VersionRegister};
 
 bitflags! {
     pub struct RedirectionEntryFlags: u64 {
-        const VECTOR               = 0x0000_00ff;
-        const DELIVERY_MODE        = 0x0000_0700;
-        const DESTINATION_MODE     = 0x0000_0800;
-        const DELIVERY_STATUS      = 0x0000_1000;
-        const POLARITY             = 0x0000_2000;
-        const REMOTE_IRR           = 0x0000_4000;
-        const TRIGGER_MODE         = 0x0000_8000;
-        const MASK                 = 0x0001_0000;
-        const RESERVED             = 0x00fe_0000;
-        const PHYSICAL_DESTINATION = 0x0f00_0000;
-        const LOGICAL_DESTINATION  = 0xff00_0000;
+        const VECTOR               = 0x0000_0000_0000_00ff;
+        const DELIVERY_MODE        = 0x0000_0000_0000_0700;
+        const DESTINATION_MODE     = 0x0000_0000_0000_0800;
+        const DELIVERY_STATUS      = 0x0000_0000_0000_1000;
+        const POLARITY             = 0x0000_0000_0000_2000;
+        const REMOTE_IRR           = 0x0000_0000_0000_4000;
+        const TRIGGER_MODE         = 0x0000_0000_0000_8000;
+        const MASK                 = 0x0000_0000_0001_0000;
+        const RESERVED             = 0x00ff_ffff_fffe_0000;
+        const PHYSICAL_DESTINATION = 0x0f00_0000_0000_0000;
+        const LOGICAL_DESTINATION  = 0xff00_0000_0000_0000;
     }
 }
 
@@ -69,13 +70,69 @@ impl RedirectionEntryFlags {
 
     pub fn destination(&self) -> Destination {
         if self.destination_mode() == DestinationMode::Logical {
-            Destination::Logical(((*self & RedirectionEntryFlags::LOGICAL_DESTINATION).bits() >> 24) as u8)
+            Destination::Logical(((*self & RedirectionEntryFlags::LOGICAL_DESTINATION).bits() >> 56) as u8)
         } else {
-            Destination::Physical(((*self & RedirectionEntryFlags::PHYSICAL_DESTINATION).bits() >> 24) as u8)
+            Destination::Physical(((*self & RedirectionEntryFlags::PHYSICAL_DESTINATION).bits() >> 56) as u8)
+        }
+    }
+
+    pub fn destination_8_bit(&self) -> Destination {
+        let destination = ((*self & RedirectionEntryFlags::LOGICAL_DESTINATION).bits() >> 56) as u8;
+        if self.destination_mode() == DestinationMode::Logical {
+            Destination::Logical(destination)
+        } else {
+            Destination::Physical(destination)
         }
     }
 }
 
+impl From<Vector> for RedirectionEntryFlags {
+    fn from(vector: Vector) -> Self {
+        Self::from_bits_truncate(vector.0 as u64) & RedirectionEntryFlags::VECTOR
+    }
+}
+
+impl From<DeliveryMode> for RedirectionEntryFlags {
+    fn from(mode: DeliveryMode) -> Self {
+        Self::from_bits_truncate(mode.as_u64() << 8)
+    }
+}
+
+impl From<DestinationMode> for RedirectionEntryFlags {
+    fn from(mode: DestinationMode) -> Self {
+        Self::from_bits_truncate(mode.as_u64() << 11)
+    }
+}
+
+impl From<Polarity> for RedirectionEntryFlags {
+    fn from(polarity: Polarity) -> Self {
+        Self::from_bits_truncate(polarity.as_u64() << 13)
+    }
+}
+
+impl From<TriggerMode> for RedirectionEntryFlags {
+    fn from(mode: TriggerMode) -> Self {
+        Self::from_bits_truncate(mode.as_u64() << 15)
+    }
+}
+
+impl From<Mask> for RedirectionEntryFlags {
+    fn from(mask: Mask) -> Self {
+        Self::from_bits_truncate(mask.as_u64() << 16)
+    }
+}
+
+impl From<Destination> for RedirectionEntryFlags {
+    fn from(destination: Destination) -> Self {
+        let (mode, id) = match destination {
+            Destination::Physical(id) => (DestinationMode::Physical, id),
+            Destination::Logical(id) => (DestinationMode::Logical, id),
+        };
+
+        Self::from_bits_truncate((id as u64) << 56) | Self::from(mode)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vector(pub u32);
 
@@ -86,6 +143,16 @@ pub enum Mask {
     Masked
 }
 
+impl Mask {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum TriggerMode {
@@ -93,6 +160,16 @@ pub enum TriggerMode {
     Level
 }
 
+impl TriggerMode {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum Polarity {
@@ -100,6 +177,16 @@ pub enum Polarity {
     ActiveLow
 }
 
+impl Polarity {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum DeliveryStatus {
@@ -107,6 +194,16 @@ pub enum DeliveryStatus {
     SendPending
 }
 
+impl DeliveryStatus {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum DestinationMode {
@@ -114,6 +211,16 @@ pub enum DestinationMode {
     Logical
 }
 
+impl DestinationMode {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum DeliveryMode {
@@ -127,6 +234,16 @@ pub enum DeliveryMode {
     ExtINT,
 }
 
+impl DeliveryMode {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
 impl TryFrom<u8> for DeliveryMode {
     type Error = &'static str;
 
@@ -151,15 +268,258 @@ pub enum Destination {
     Logical(u8),
 }
 
+impl Destination {
+    pub fn mode(&self) -> DestinationMode {
+        match *self {
+            Destination::Physical(_) => DestinationMode::Physical,
+            Destination::Logical(_) => DestinationMode::Logical,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PhysicalDestinationWidth {
+    FourBit,
+    EightBit,
+}
+
+impl PhysicalDestinationWidth {
+    pub fn max_id(self) -> u8 {
+        match self {
+            PhysicalDestinationWidth::FourBit => 0xf,
+            PhysicalDestinationWidth::EightBit => 0xff,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum DeliveryError {
+    ReservedDeliveryMode,
+    VectorOutOfRange,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RedirectionEntry {
+    pub vector: Vector,
+    pub delivery_mode: DeliveryMode,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+    pub mask: Mask,
+    pub destination: Destination,
+}
+
+impl RedirectionEntry {
+    pub const MIN_VECTOR: Vector = Vector(0x10);
+    pub const MAX_VECTOR: Vector = Vector(0xfe);
+
+    /// Delivery checks shared by redirection entries, msi messages and remapping table entries.
+    pub(crate) fn check_delivery(vector: Vector, delivery_mode: DeliveryMode) -> Result<(), DeliveryError> {
+        match delivery_mode {
+            DeliveryMode::Reserved0 | DeliveryMode::Reserved1 => Err(DeliveryError::ReservedDeliveryMode),
+            DeliveryMode::Fixed | DeliveryMode::LowestPriority
+                if vector.0 < Self::MIN_VECTOR.0 || vector.0 > Self::MAX_VECTOR.0 => {
+                Err(DeliveryError::VectorOutOfRange)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn new(vector: Vector, destination: Destination) -> Self {
+        RedirectionEntry {
+            vector,
+            delivery_mode: DeliveryMode::Fixed,
+            polarity: Polarity::ActiveHigh,
+            trigger_mode: TriggerMode::Edge,
+            mask: Mask::NotMasked,
+            destination,
+        }
+    }
+
+    pub fn from_flags(flags: RedirectionEntryFlags, width: PhysicalDestinationWidth) -> Self {
+        let destination = match width {
+            PhysicalDestinationWidth::FourBit => flags.destination(),
+            PhysicalDestinationWidth::EightBit => flags.destination_8_bit(),
+        };
+
+        RedirectionEntry {
+            vector: flags.vector(),
+            delivery_mode: flags.delivery_mode(),
+            polarity: flags.polarity(),
+            trigger_mode: flags.trigger_mode(),
+            mask: flags.mask(),
+            destination,
+        }
+    }
+
+    pub fn with_vector(mut self, vector: Vector) -> Self {
+        self.vector = vector;
+        self
+    }
+
+    pub fn with_delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
+        self.delivery_mode = delivery_mode;
+        self
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn with_trigger_mode(mut self, trigger_mode: TriggerMode) -> Self {
+        self.trigger_mode = trigger_mode;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn with_destination(mut self, destination: Destination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    pub fn destination_mode(&self) -> DestinationMode {
+        self.destination.mode()
+    }
+
+    pub fn validate(&self, width: PhysicalDestinationWidth) -> Result<(), &'static str> {
+        Self::check_delivery(self.vector, self.delivery_mode).map_err(|error| match error {
+            DeliveryError::ReservedDeliveryMode => "reserved delivery mode",
+            DeliveryError::VectorOutOfRange => "vector out of range",
+        })?;
+
+        if let Destination::Physical(id) = self.destination {
+            if id > width.max_id() {
+                return Err("physical destination out of range");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_flags(&self, width: PhysicalDestinationWidth) -> Result<RedirectionEntryFlags, &'static str> {
+        self.validate(width)?;
+
+        Ok(RedirectionEntryFlags::from(self.vector)
+            | RedirectionEntryFlags::from(self.delivery_mode)
+            | RedirectionEntryFlags::from(self.polarity)
+            | RedirectionEntryFlags::from(self.trigger_mode)
+            | RedirectionEntryFlags::from(self.mask)
+            | RedirectionEntryFlags::from(self.destination))
+    }
+}
+
+impl From<RedirectionEntryFlags> for RedirectionEntry {
+    fn from(flags: RedirectionEntryFlags) -> Self {
+        RedirectionEntry::from_flags(flags, PhysicalDestinationWidth::FourBit)
+    }
+}
+
+impl TryFrom<RedirectionEntry> for RedirectionEntryFlags {
+    type Error = &'static str;
+
+    fn try_from(entry: RedirectionEntry) -> Result<Self, Self::Error> {
+        entry.to_flags(PhysicalDestinationWidth::FourBit)
+    }
+}
+
 pub struct RedirectionEntryRegister(pub u32);
+
+impl RedirectionEntryRegister {
+    pub fn new(pin: u32, version: VersionFlags) -> Result<Self, &'static str> {
+        IoApic64BitRegisterIndex::redirection_entry(pin, version).map(|index| RedirectionEntryRegister(index.pin()))
+    }
+
+    pub unsafe fn all(apic: &dyn IoApic) -> Result<impl Iterator<Item = RedirectionEntryRegister>, RegisterError> {
+        let version = VersionRegister.read(apic)?;
+
+        Ok(IoApic64BitRegisterIndex::redirection_entries(version).map(|index| RedirectionEntryRegister(index.pin())))
+    }
+
+    pub fn index(&self) -> IoApic64BitRegisterIndex {
+        IoApic64BitRegisterIndex::RedirectionEntry(self.0)
+    }
+
+    fn checked_index(&self) -> Result<IoApic64BitRegisterIndex, RegisterError> {
+        let index = self.index();
+        index.low_register().map(|_| index).map_err(|_| RegisterError::InvalidRedirectionEntry(self.0))
+    }
+}
+
 impl IoApicRegister for RedirectionEntryRegister {
     type Value = RedirectionEntryFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn IoApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(self.0)))
+impl ReadableIoApicRegister for RedirectionEntryRegister {
+    unsafe fn read(&self, apic: &dyn IoApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.read_reg_64(self.checked_index()?)))
     }
+}
+
+impl WritableIoApicRegister for RedirectionEntryRegister {
+    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.write_reg_64(self.checked_index()?, value.bits());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_entry_to_flags() {
+        let entry = RedirectionEntry::new(Vector(0x31), Destination::Physical(3))
+            .with_polarity(Polarity::ActiveLow)
+            .with_trigger_mode(TriggerMode::Level);
+        let flags = RedirectionEntryFlags::try_from(entry).expect("flags");
+
+        assert_eq!(flags.bits(), 0x0300_0000_0000_a031);
+        assert_eq!(RedirectionEntry::from(flags), entry);
+    }
+
+    #[test]
+    pub fn test_logical_destination() {
+        let entry = RedirectionEntry::new(Vector(0x40), Destination::Logical(0xf0))
+            .with_delivery_mode(DeliveryMode::LowestPriority)
+            .with_mask(Mask::Masked);
+        let flags = RedirectionEntryFlags::try_from(entry).expect("flags");
 
-    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value) {
-        apic.write_reg_64(IoApic64BitRegisterIndex::RedirectionEntry(self.0), value.bits())
+        assert_eq!(flags.bits(), 0xf000_0000_0001_0940);
+        assert_eq!(flags.destination_mode(), DestinationMode::Logical);
+        assert_eq!(RedirectionEntry::from(flags), entry);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_validation() {
+        let entry = RedirectionEntry::new(Vector(0x0f), Destination::Physical(0));
+        assert!(entry.validate(PhysicalDestinationWidth::FourBit).is_err());
+        assert!(entry.with_vector(Vector(0xff)).validate(PhysicalDestinationWidth::FourBit).is_err());
+        assert!(entry.with_delivery_mode(DeliveryMode::NMI).validate(PhysicalDestinationWidth::FourBit).is_ok());
+        assert!(entry.with_delivery_mode(DeliveryMode::Reserved0).validate(PhysicalDestinationWidth::FourBit).is_err());
+
+        let entry = RedirectionEntry::new(Vector(0x20), Destination::Physical(0x20));
+        assert!(RedirectionEntryFlags::try_from(entry).is_err());
+
+        let flags = entry.to_flags(PhysicalDestinationWidth::EightBit).expect("flags");
+        assert_eq!(RedirectionEntry::from_flags(flags, PhysicalDestinationWidth::EightBit), entry);
+    }
+
+    #[test]
+    pub fn test_register_rejects_invalid_selector() {
+        use crate::io::emulated::EmulatedIoApic;
+
+        let ioapic = EmulatedIoApic::new(0, 24);
+        let register = RedirectionEntryRegister(IoApic64BitRegisterIndex::MAX_PIN + 1);
+        unsafe {
+            assert_eq!(register.read(&ioapic), Err(RegisterError::InvalidRedirectionEntry(0x78)));
+            assert_eq!(
+                register.write(&ioapic, RedirectionEntryFlags::MASK),
+                Err(RegisterError::InvalidRedirectionEntry(0x78))
+            );
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::io::IoApic;
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::io::{IoApic, VersionFlags};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u32)]
@@ -24,20 +26,118 @@ pub enum IoApic64BitRegisterIndex {
 }
 
 impl IoApic64BitRegisterIndex {
-    pub fn as_u32(&self) -> u32 {
+    /// The last pin whose redirection entry fits in the 8 bit register select.
+    pub const MAX_PIN: u32 = 0x77;
+
+    pub fn redirection_entry(pin: u32, version: VersionFlags) -> Result<Self, &'static str> {
+        if pin > version.max_redirect_entry() || pin > Self::MAX_PIN {
+            Err("redirection entry out of range")
+        } else {
+            Ok(IoApic64BitRegisterIndex::RedirectionEntry(pin))
+        }
+    }
+
+    pub fn redirection_entries(version: VersionFlags) -> RedirectionEntryIndexes {
+        RedirectionEntryIndexes {
+            next: 0,
+            end: version.max_redirect_entry().min(Self::MAX_PIN) + 1,
+        }
+    }
+
+    pub fn pin(&self) -> u32 {
         match *self {
-            IoApic64BitRegisterIndex::RedirectionEntry(value) => value
+            IoApic64BitRegisterIndex::RedirectionEntry(pin) => pin
         }
     }
 
-    pub fn as_u64(&self) -> u64 {
-        self.as_u32() as u64
+    pub fn low_register(&self) -> Result<u32, &'static str> {
+        match *self {
+            IoApic64BitRegisterIndex::RedirectionEntry(pin) if pin <= Self::MAX_PIN => Ok(0x10 + 2 * pin),
+            IoApic64BitRegisterIndex::RedirectionEntry(_) => Err("redirection entry out of range"),
+        }
+    }
+
+    pub fn high_register(&self) -> Result<u32, &'static str> {
+        self.low_register().map(|register| register + 1)
+    }
+
+    pub fn as_u32(&self) -> Result<u32, &'static str> {
+        self.low_register()
+    }
+
+    pub fn as_u64(&self) -> Result<u64, &'static str> {
+        self.as_u32().map(|register| register as u64)
+    }
+}
+
+pub struct RedirectionEntryIndexes {
+    next: u32,
+    end: u32,
+}
+
+impl Iterator for RedirectionEntryIndexes {
+    type Item = IoApic64BitRegisterIndex;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < self.end {
+            self.next += 1;
+            Some(IoApic64BitRegisterIndex::RedirectionEntry(self.next - 1))
+        } else {
+            None
+        }
     }
 }
 
 pub trait IoApicRegister {
     type Value;
+}
+
+pub trait ReadableIoApicRegister: IoApicRegister {
+    unsafe fn read(&self, apic: &dyn IoApic) -> Result<Self::Value, RegisterError>;
+}
+
+pub trait WritableIoApicRegister: IoApicRegister {
+    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value) -> Result<(), RegisterError>;
+}
+
+pub trait ReadWriteIoApicRegister: ReadableIoApicRegister + WritableIoApicRegister {}
+
+impl<R: ReadableIoApicRegister + WritableIoApicRegister> ReadWriteIoApicRegister for R {}
 
-    unsafe fn read(&self, apic: &dyn IoApic) -> Self::Value;
-    unsafe fn write(&self, apic: &dyn IoApic, value: Self::Value);
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_redirection_entry_registers() {
+        let index = IoApic64BitRegisterIndex::RedirectionEntry(0);
+        assert_eq!(index.low_register(), Ok(0x10));
+        assert_eq!(index.high_register(), Ok(0x11));
+
+        let index = IoApic64BitRegisterIndex::RedirectionEntry(23);
+        assert_eq!(index.pin(), 23);
+        assert_eq!(index.as_u32(), Ok(0x3e));
+        assert_eq!(index.high_register(), Ok(0x3f));
+
+        let index = IoApic64BitRegisterIndex::RedirectionEntry(IoApic64BitRegisterIndex::MAX_PIN);
+        assert_eq!(index.high_register(), Ok(0xff));
+        assert!(IoApic64BitRegisterIndex::RedirectionEntry(0x78).low_register().is_err());
+        assert!(IoApic64BitRegisterIndex::RedirectionEntry(u32::MAX).high_register().is_err());
+    }
+
+    #[test]
+    pub fn test_redirection_entry_validation() {
+        let version = VersionFlags::from_bits_truncate(0x0017_0011);
+
+        assert_eq!(IoApic64BitRegisterIndex::redirection_entry(23, version),
+            Ok(IoApic64BitRegisterIndex::RedirectionEntry(23)));
+        assert!(IoApic64BitRegisterIndex::redirection_entry(24, version).is_err());
+
+        let pins: Vec<u32> = IoApic64BitRegisterIndex::redirection_entries(version).map(|index| index.pin()).collect();
+        assert_eq!(pins, (0..24).collect::<Vec<u32>>());
+
+        let version = VersionFlags::from_bits_truncate(0x00ff_0020);
+        assert!(IoApic64BitRegisterIndex::redirection_entry(0x78, version).is_err());
+        assert_eq!(IoApic64BitRegisterIndex::redirection_entries(version).count(), 0x78);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::io::{IoApic, IoApicRegister, IoApic32BitRegisterIndex};
+use crate::error::RegisterError;
+use crate::io::{IoApic, IoApicRegister, ReadableIoApicRegister, IoApic32BitRegisterIndex};
 
 bitflags! {
     pub struct VersionFlags: u32 {
@@ -16,20 +17,31 @@ impl VersionFlags {
     pub fn max_redirect_entry(&self) -> u32 {
         (*self & VersionFlags::MAX_REDIRECT_ENTRY).bits() >> 16
     }
+
+    pub fn has_eoi_register(&self) -> bool {
+        self.version().0 >= 0x20
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Version(pub u32);
 
+/// The version register is read only, so it has no typed write:
+///
+/// ```compile_fail
+/// use apic_types::io::{IoApic, VersionFlags, VersionRegister, WritableIoApicRegister};
+///
+/// unsafe fn clobber(ioapic: &dyn IoApic) {
+///     VersionRegister.write(ioapic, VersionFlags::empty());
+/// }
+/// ```
 pub struct VersionRegister;
 impl IoApicRegister for VersionRegister {
     type Value = VersionFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn IoApic) -> Self::Value {
-        VersionFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::Version))
-    }
-
-    unsafe fn write(&self, _apic: &dyn IoApic, _value: Self::Value) {
-        panic!("read only register");
+impl ReadableIoApicRegister for VersionRegister {
+    unsafe fn read(&self, apic: &dyn IoApic) -> Result<Self::Value, RegisterError> {
+        Ok(VersionFlags::from_bits_truncate(apic.read_reg_32(IoApic32BitRegisterIndex::Version)))
     }
 }
\ No newline at end of file
@@ -1,10 +1,21 @@
 #![cfg_attr(not(test), no_std)]
+// every register accessor is unsafe for the same reason: the backend must
+// refer to a live, correctly mapped apic. that contract is stated once on the
+// LocalApic and IoApic traits rather than repeated on each register.
+#![allow(clippy::missing_safety_doc)]
 
 #[macro_use]
 extern crate bitflags;
 
+pub mod eoi;
+pub mod error;
 pub mod local;
 pub mod io;
+pub mod madt;
+pub mod msi;
+pub mod msr;
+pub mod pic;
+pub mod remapping;
 
 #[cfg(test)]
 mod tests {
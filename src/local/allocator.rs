@@ -0,0 +1,207 @@
+use core::result::Result;
+use crate::local::{InterruptVector, InterruptVectorSet, PriorityClass, SivrFlags};
+
+pub const EXCEPTION_VECTORS: u32 = 32;
+// a block never spans priority classes, which hold 16 vectors each
+pub const MAX_BLOCK_SIZE: u32 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VectorError {
+    InvalidVector(InterruptVector),
+    InvalidPriorityClass(PriorityClass),
+    InvalidBlockSize(u32),
+    Reserved(InterruptVector),
+    AlreadyAllocated(InterruptVector),
+    NotAllocated(InterruptVector),
+    Exhausted(PriorityClass),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VectorAllocator {
+    reserved: InterruptVectorSet,
+    allocated: InterruptVectorSet,
+}
+
+impl VectorAllocator {
+    pub fn new(spurious: SivrFlags) -> Self {
+        let mut reserved = InterruptVectorSet::new();
+        for vector in 0..EXCEPTION_VECTORS {
+            reserved.insert(InterruptVector(vector));
+        }
+        reserved.insert(spurious.vector());
+
+        VectorAllocator {
+            reserved,
+            allocated: InterruptVectorSet::new(),
+        }
+    }
+
+    pub fn reserve(&mut self, vector: InterruptVector) -> Result<(), VectorError> {
+        Self::check_vector(vector)?;
+        self.check_free(vector)?;
+
+        self.reserved.insert(vector);
+        Ok(())
+    }
+
+    pub fn is_reserved(&self, vector: InterruptVector) -> bool {
+        self.reserved.contains(vector)
+    }
+
+    pub fn is_allocated(&self, vector: InterruptVector) -> bool {
+        self.allocated.contains(vector)
+    }
+
+    pub fn allocated(&self) -> InterruptVectorSet {
+        self.allocated
+    }
+
+    pub fn allocate(&mut self, class: PriorityClass) -> Result<InterruptVector, VectorError> {
+        self.allocate_block(class, 1)
+    }
+
+    pub fn allocate_vector(&mut self, vector: InterruptVector) -> Result<InterruptVector, VectorError> {
+        Self::check_vector(vector)?;
+        self.check_free(vector)?;
+
+        self.allocated.insert(vector);
+        Ok(vector)
+    }
+
+    pub fn allocate_block(&mut self, class: PriorityClass, count: u32) -> Result<InterruptVector, VectorError> {
+        if class.0 > 0xf {
+            return Err(VectorError::InvalidPriorityClass(class));
+        }
+
+        if count == 0 || count > MAX_BLOCK_SIZE || !count.is_power_of_two() {
+            return Err(VectorError::InvalidBlockSize(count));
+        }
+
+        // msi requires the block to be aligned to its size
+        let first = class.0 * 16;
+        let base = (first..first + 16)
+            .step_by(count as usize)
+            .filter(|base| base % count == 0 && base + count <= first + 16)
+            .find(|base| (*base..base + count).all(|vector| self.is_free(InterruptVector(vector))))
+            .ok_or(VectorError::Exhausted(class))?;
+
+        for vector in base..base + count {
+            self.allocated.insert(InterruptVector(vector));
+        }
+
+        Ok(InterruptVector(base))
+    }
+
+    pub fn free(&mut self, vector: InterruptVector) -> Result<(), VectorError> {
+        self.free_block(vector, 1)
+    }
+
+    pub fn free_block(&mut self, base: InterruptVector, count: u32) -> Result<(), VectorError> {
+        if count == 0 || count > MAX_BLOCK_SIZE || !count.is_power_of_two() {
+            return Err(VectorError::InvalidBlockSize(count));
+        }
+
+        if base.0 > 0xff || base.0 + count > 0x100 {
+            return Err(VectorError::InvalidVector(base));
+        }
+
+        for vector in base.0..base.0 + count {
+            if !self.allocated.contains(InterruptVector(vector)) {
+                return Err(VectorError::NotAllocated(InterruptVector(vector)));
+            }
+        }
+
+        for vector in base.0..base.0 + count {
+            self.allocated.remove(InterruptVector(vector));
+        }
+        Ok(())
+    }
+
+    fn is_free(&self, vector: InterruptVector) -> bool {
+        !self.reserved.contains(vector) && !self.allocated.contains(vector)
+    }
+
+    fn check_vector(vector: InterruptVector) -> Result<(), VectorError> {
+        if vector.0 < EXCEPTION_VECTORS || vector.0 > 0xff {
+            Err(VectorError::InvalidVector(vector))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_free(&self, vector: InterruptVector) -> Result<(), VectorError> {
+        if self.reserved.contains(vector) {
+            Err(VectorError::Reserved(vector))
+        } else if self.allocated.contains(vector) {
+            Err(VectorError::AlreadyAllocated(vector))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for VectorAllocator {
+    fn default() -> Self {
+        VectorAllocator::new(SivrFlags::VECTOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_reserved_vectors() {
+        let mut allocator = VectorAllocator::default();
+
+        assert!(allocator.is_reserved(InterruptVector(0x0e)));
+        assert!(allocator.is_reserved(InterruptVector(0xff)));
+        assert_eq!(allocator.allocate(PriorityClass(1)), Err(VectorError::Exhausted(PriorityClass(1))));
+        assert_eq!(allocator.reserve(InterruptVector(0x10)), Err(VectorError::InvalidVector(InterruptVector(0x10))));
+
+        allocator.reserve(InterruptVector(0xfe)).expect("reserve");
+        assert_eq!(allocator.allocate_vector(InterruptVector(0xfe)), Err(VectorError::Reserved(InterruptVector(0xfe))));
+    }
+
+    #[test]
+    pub fn test_allocate_by_class() {
+        let mut allocator = VectorAllocator::default();
+
+        let vector = allocator.allocate(PriorityClass(5)).expect("vector");
+        assert_eq!(vector, InterruptVector(0x50));
+        assert_eq!(vector.priority_class(), PriorityClass(5));
+        assert_eq!(allocator.allocate(PriorityClass(5)), Ok(InterruptVector(0x51)));
+
+        // the spurious vector is skipped in the top class
+        for _ in 0..15 {
+            allocator.allocate(PriorityClass(15)).expect("vector");
+        }
+        assert_eq!(allocator.allocate(PriorityClass(15)), Err(VectorError::Exhausted(PriorityClass(15))));
+        assert!(!allocator.is_allocated(InterruptVector(0xff)));
+
+        allocator.free(vector).expect("free");
+        assert_eq!(allocator.free(vector), Err(VectorError::NotAllocated(vector)));
+        assert_eq!(allocator.allocate(PriorityClass(5)), Ok(InterruptVector(0x50)));
+    }
+
+    #[test]
+    pub fn test_allocate_blocks() {
+        let mut allocator = VectorAllocator::default();
+
+        allocator.allocate_vector(InterruptVector(0x41)).expect("vector");
+        assert_eq!(allocator.allocate_block(PriorityClass(4), 4), Ok(InterruptVector(0x44)));
+        assert_eq!(allocator.allocate_block(PriorityClass(4), 8), Ok(InterruptVector(0x48)));
+        assert_eq!(allocator.allocate_block(PriorityClass(6), 16), Ok(InterruptVector(0x60)));
+        assert!(allocator.is_allocated(InterruptVector(0x6f)));
+        assert!(!allocator.is_allocated(InterruptVector(0x70)));
+        assert_eq!(allocator.allocate_block(PriorityClass(6), 32), Err(VectorError::InvalidBlockSize(32)));
+        assert_eq!(allocator.allocate_block(PriorityClass(4), 3), Err(VectorError::InvalidBlockSize(3)));
+        assert_eq!(allocator.allocate_block(PriorityClass(15), 16), Err(VectorError::Exhausted(PriorityClass(15))));
+
+        allocator.free_block(InterruptVector(0x60), 16).expect("free");
+        assert!(!allocator.is_allocated(InterruptVector(0x6f)));
+
+        assert_eq!(allocator.free_block(InterruptVector(0xfc), 8), Err(VectorError::InvalidVector(InterruptVector(0xfc))));
+        assert_eq!(allocator.free(InterruptVector(u32::MAX)), Err(VectorError::InvalidVector(InterruptVector(u32::MAX))));
+    }
+}
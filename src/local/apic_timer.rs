@@ -0,0 +1,216 @@
+use core::convert::TryFrom;
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::msr::Msr;
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, WritableLocalApicRegister, InterruptVector, LvtFlags, LvtMask,
+    LvtTimerMode, LvtTimerRegister, LvtTimerDivideValue, TimerDivideConfigurationFlags,
+    LvtTimerDivideConfigurationRegister, LvtTimerInitialCount, LvtTimerInitialCountRegister,
+    LvtTimerCurrentCountRegister, TscDeadline, TscDeadlineRegister,
+};
+
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+const DIVIDERS: [u32; 8] = [1, 2, 4, 8, 16, 32, 64, 128];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimerError {
+    InvalidFrequency,
+    InvalidVector(InterruptVector),
+    DurationTooLong(u64),
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for TimerError {
+    fn from(error: RegisterError) -> Self {
+        TimerError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerFrequency(pub u64);
+
+impl TimerFrequency {
+    pub fn ticks(&self, divider: LvtTimerDivideValue, nanoseconds: u64) -> u64 {
+        let ticks = nanoseconds as u128 * self.0 as u128 / (divider.0 as u128 * NANOSECONDS_PER_SECOND);
+        ticks.min(u64::MAX as u128) as u64
+    }
+
+    pub fn nanoseconds(&self, divider: LvtTimerDivideValue, ticks: u32) -> Result<u64, TimerError> {
+        if self.0 == 0 {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        let nanoseconds = ticks as u128 * divider.0 as u128 * NANOSECONDS_PER_SECOND / self.0 as u128;
+        Ok(nanoseconds.min(u64::MAX as u128) as u64)
+    }
+
+    pub fn best_divider(&self, nanoseconds: u64) -> Result<(LvtTimerDivideValue, LvtTimerInitialCount), TimerError> {
+        if self.0 == 0 {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        DIVIDERS.iter()
+            .map(|divider| LvtTimerDivideValue(*divider))
+            .find(|divider| self.ticks(*divider, nanoseconds) <= u32::MAX as u64)
+            .map(|divider| (divider, LvtTimerInitialCount(self.ticks(divider, nanoseconds).max(1) as u32)))
+            .ok_or(TimerError::DurationTooLong(nanoseconds))
+    }
+}
+
+pub struct ApicTimer<'a> {
+    apic: &'a dyn LocalApic,
+    frequency: TimerFrequency,
+    vector: InterruptVector,
+}
+
+impl<'a> ApicTimer<'a> {
+    pub fn new(apic: &'a dyn LocalApic, frequency: TimerFrequency, vector: InterruptVector) -> Result<Self, TimerError> {
+        if frequency.0 == 0 {
+            return Err(TimerError::InvalidFrequency);
+        }
+        if vector.0 < 0x10 || vector.0 > 0xff {
+            return Err(TimerError::InvalidVector(vector));
+        }
+
+        Ok(ApicTimer { apic, frequency, vector })
+    }
+
+    pub fn frequency(&self) -> TimerFrequency {
+        self.frequency
+    }
+
+    pub fn vector(&self) -> InterruptVector {
+        self.vector
+    }
+
+    pub unsafe fn one_shot(&self, nanoseconds: u64) -> Result<(), TimerError> {
+        self.arm(LvtTimerMode::OneShot, nanoseconds)
+    }
+
+    pub unsafe fn periodic(&self, nanoseconds: u64) -> Result<(), TimerError> {
+        self.arm(LvtTimerMode::Periodic, nanoseconds)
+    }
+
+    pub unsafe fn tsc_deadline(&self, msr: &dyn Msr, deadline: TscDeadline) -> Result<(), TimerError> {
+        LvtTimerRegister.write(self.apic, self.lvt(LvtTimerMode::TSCDeadline))?;
+        // an xapic lvt write is a plain store that the wrmsr below may pass,
+        // so fence it before arming the deadline (sdm 10.5.4.1)
+        #[cfg(target_arch = "x86_64")]
+        core::arch::x86_64::_mm_mfence();
+        TscDeadlineRegister.write(msr, deadline);
+        Ok(())
+    }
+
+    pub unsafe fn stop(&self) -> Result<(), TimerError> {
+        let lvt = LvtTimerRegister.read(self.apic)?;
+
+        LvtTimerRegister.write(self.apic, lvt | LvtFlags::from(LvtMask::Masked))?;
+        if lvt.timer_mode_2_bit()? == LvtTimerMode::TSCDeadline {
+            return Ok(());
+        }
+
+        LvtTimerInitialCountRegister.write(self.apic, LvtTimerInitialCount(0))?;
+        Ok(())
+    }
+
+    pub unsafe fn remaining(&self) -> Result<u64, TimerError> {
+        let divider = LvtTimerDivideValue::from(LvtTimerDivideConfigurationRegister.read(self.apic)?);
+        let count = LvtTimerCurrentCountRegister.read(self.apic)?;
+
+        self.frequency.nanoseconds(divider, count.0)
+    }
+
+    pub unsafe fn remaining_tsc(&self, msr: &dyn Msr, now: u64) -> u64 {
+        TscDeadlineRegister.read(msr).0.saturating_sub(now)
+    }
+
+    unsafe fn arm(&self, mode: LvtTimerMode, nanoseconds: u64) -> Result<(), TimerError> {
+        let (divider, count) = self.frequency.best_divider(nanoseconds)?;
+        let divide_configuration = TimerDivideConfigurationFlags::try_from(divider)
+            .map_err(|_| RegisterError::ReservedValue { field: "divide value", value: divider.0 })?;
+
+        LvtTimerRegister.write(self.apic, self.lvt(mode))?;
+        LvtTimerDivideConfigurationRegister.write(self.apic, divide_configuration)?;
+        LvtTimerInitialCountRegister.write(self.apic, count)?;
+
+        Ok(())
+    }
+
+    fn lvt(&self, mode: LvtTimerMode) -> LvtFlags {
+        LvtFlags::from_bits_truncate(self.vector.0 & 0xff) | LvtFlags::from(mode) | LvtFlags::from(LvtMask::NotMasked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msr::FakeMsr;
+    use crate::local::{EmulatedLocalApic, SivrFlags, SpuriousInterruptVectorRegister};
+
+    #[test]
+    pub fn test_best_divider() {
+        let frequency = TimerFrequency(100_000_000);
+
+        assert_eq!(frequency.best_divider(1_000), Ok((LvtTimerDivideValue(1), LvtTimerInitialCount(100))));
+        assert_eq!(frequency.best_divider(1), Ok((LvtTimerDivideValue(1), LvtTimerInitialCount(1))));
+        assert_eq!(frequency.best_divider(60_000_000_000), Ok((LvtTimerDivideValue(2), LvtTimerInitialCount(3_000_000_000))));
+        assert_eq!(frequency.best_divider(6_000_000_000_000), Err(TimerError::DurationTooLong(6_000_000_000_000)));
+        assert_eq!(TimerFrequency(0).best_divider(1_000), Err(TimerError::InvalidFrequency));
+        assert_eq!(TimerFrequency(0).nanoseconds(LvtTimerDivideValue(1), 100), Err(TimerError::InvalidFrequency));
+        assert_eq!(frequency.nanoseconds(LvtTimerDivideValue(2), 100), Ok(2_000));
+    }
+
+    #[test]
+    pub fn test_one_shot() {
+        let apic = EmulatedLocalApic::new(0);
+        let timer = ApicTimer::new(&apic, TimerFrequency(100_000_000), InterruptVector(0x40)).unwrap();
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::from_bits_truncate(0x1ff)).unwrap();
+            timer.one_shot(10_000).unwrap();
+            assert_eq!(timer.remaining(), Ok(10_000));
+
+            apic.advance_timer(400);
+            assert_eq!(timer.remaining(), Ok(6_000));
+
+            apic.advance_timer(600);
+            assert_eq!(timer.remaining(), Ok(0));
+            assert!(apic.interrupt_request().contains(InterruptVector(0x40)));
+        }
+    }
+
+    #[test]
+    pub fn test_periodic_and_stop() {
+        let apic = EmulatedLocalApic::new(0);
+        let timer = ApicTimer::new(&apic, TimerFrequency(100_000_000), InterruptVector(0x40)).unwrap();
+
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::from_bits_truncate(0x1ff)).unwrap();
+            timer.periodic(1_000).unwrap();
+            apic.advance_timer(150);
+            assert_eq!(timer.remaining(), Ok(500));
+
+            timer.stop().unwrap();
+            assert_eq!(timer.remaining(), Ok(0));
+            assert_eq!(LvtTimerRegister.read(&apic).unwrap().mask(), LvtMask::Masked);
+        }
+    }
+
+    #[test]
+    pub fn test_tsc_deadline() {
+        let apic = EmulatedLocalApic::new(0);
+        let msr = FakeMsr::default();
+        let timer = ApicTimer::new(&apic, TimerFrequency(100_000_000), InterruptVector(0x40)).unwrap();
+
+        unsafe {
+            timer.tsc_deadline(&msr, TscDeadline(5_000)).unwrap();
+            assert_eq!(LvtTimerRegister.read(&apic).unwrap().timer_mode_2_bit(), Ok(LvtTimerMode::TSCDeadline));
+            assert_eq!(timer.remaining_tsc(&msr, 1_000), 4_000);
+            assert_eq!(timer.remaining_tsc(&msr, 6_000), 0);
+        }
+
+        assert!(ApicTimer::new(&apic, TimerFrequency(0), InterruptVector(0x40)).is_err());
+        assert_eq!(ApicTimer::new(&apic, TimerFrequency(1), InterruptVector(0x0f)).err(),
+            Some(TimerError::InvalidVector(InterruptVector(0x0f))));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
 
 bitflags! {
     pub struct ArbitrationPriorityFlags: u32 {
@@ -33,13 +34,12 @@ impl From<PrioritySubClass> for ArbitrationPriorityFlags {
 pub struct ArbitrationPriorityRegister;
 impl LocalApicRegister for ArbitrationPriorityRegister {
     type Value = ArbitrationPriorityFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        ArbitrationPriorityFlags::from_bits(apic.read_reg_32(LocalApicRegisterIndex::ArbitrationPriority)).unwrap()
-    }
-
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::ArbitrationPriority, ArbitrationPriorityFlags::from(value).bits());
+impl ReadableLocalApicRegister for ArbitrationPriorityRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let value = apic.try_read_reg_32(LocalApicRegisterIndex::ArbitrationPriority)?;
+        ArbitrationPriorityFlags::from_bits(value).ok_or(RegisterError::UndefinedBits(value as u64))
     }
 }
 
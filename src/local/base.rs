@@ -0,0 +1,163 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::msr::Msr;
+
+bitflags! {
+    pub struct ApicBaseFlags: u64 {
+        const RESERVED       = 0xfff0_0000_0000_02ff;
+        const BSP            = 0x0000_0000_0000_0100;
+        const X2APIC_ENABLE  = 0x0000_0000_0000_0400;
+        const GLOBAL_ENABLE  = 0x0000_0000_0000_0800;
+        const BASE           = 0x000f_ffff_ffff_f000;
+    }
+}
+
+impl ApicBaseFlags {
+    pub fn is_bsp(&self) -> bool {
+        self.contains(ApicBaseFlags::BSP)
+    }
+
+    pub fn base(&self) -> u64 {
+        (*self & ApicBaseFlags::BASE).bits()
+    }
+
+    pub fn with_base(self, base: u64) -> Result<Self, &'static str> {
+        if base & !ApicBaseFlags::BASE.bits() != 0 {
+            Err("apic base must be page aligned and within the physical address range")
+        } else {
+            Ok((self - ApicBaseFlags::BASE) | ApicBaseFlags::from_bits_truncate(base))
+        }
+    }
+
+    pub fn mode(&self) -> Result<ApicMode, RegisterError> {
+        match (self.contains(ApicBaseFlags::GLOBAL_ENABLE), self.contains(ApicBaseFlags::X2APIC_ENABLE)) {
+            (false, false) => Ok(ApicMode::Disabled),
+            (true, false) => Ok(ApicMode::XApic),
+            (true, true) => Ok(ApicMode::X2Apic),
+            (false, true) => Err(RegisterError::ReservedValue {
+                field: "apic mode",
+                value: ((*self & (ApicBaseFlags::GLOBAL_ENABLE | ApicBaseFlags::X2APIC_ENABLE)).bits() >> 10) as u32,
+            }),
+        }
+    }
+
+    pub fn with_mode(self, mode: ApicMode) -> Self {
+        let enable = ApicBaseFlags::GLOBAL_ENABLE | ApicBaseFlags::X2APIC_ENABLE;
+        (self - enable) | ApicBaseFlags::from(mode)
+    }
+
+    pub fn transition(self, to: ApicMode) -> Result<Self, ApicModeError> {
+        let from = self.mode()?;
+
+        if from.can_transition_to(to) {
+            Ok(self.with_mode(to))
+        } else {
+            Err(ApicModeError::IllegalTransition { from, to })
+        }
+    }
+}
+
+impl From<ApicMode> for ApicBaseFlags {
+    fn from(mode: ApicMode) -> Self {
+        match mode {
+            ApicMode::Disabled => ApicBaseFlags::empty(),
+            ApicMode::XApic => ApicBaseFlags::GLOBAL_ENABLE,
+            ApicMode::X2Apic => ApicBaseFlags::GLOBAL_ENABLE | ApicBaseFlags::X2APIC_ENABLE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApicMode {
+    Disabled,
+    XApic,
+    X2Apic,
+}
+
+impl ApicMode {
+    pub fn can_transition_to(self, to: ApicMode) -> bool {
+        match (self, to) {
+            (ApicMode::Disabled, ApicMode::X2Apic) => false,
+            // x2apic can only be left by disabling the apic and resetting
+            (ApicMode::X2Apic, ApicMode::XApic) => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApicModeError {
+    IllegalTransition { from: ApicMode, to: ApicMode },
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for ApicModeError {
+    fn from(error: RegisterError) -> Self {
+        ApicModeError::Register(error)
+    }
+}
+
+pub struct ApicBaseRegister;
+
+impl ApicBaseRegister {
+    pub const MSR: u32 = 0x1b;
+
+    pub unsafe fn read(&self, msr: &dyn Msr) -> Result<ApicBaseFlags, RegisterError> {
+        let value = ApicBaseFlags::from_bits_truncate(msr.read_msr(Self::MSR));
+        value.mode().map(|_| value)
+    }
+
+    pub unsafe fn write(&self, msr: &dyn Msr, value: ApicBaseFlags) {
+        msr.write_msr(Self::MSR, (value - ApicBaseFlags::RESERVED).bits());
+    }
+
+    pub unsafe fn set_mode(&self, msr: &dyn Msr, mode: ApicMode) -> Result<ApicBaseFlags, ApicModeError> {
+        let value = self.read(msr)?.transition(mode)?;
+        self.write(msr, value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msr::FakeMsr;
+
+    #[test]
+    pub fn test_decode() {
+        let flags = ApicBaseFlags::from_bits_truncate(0xfee0_0900);
+
+        assert!(flags.is_bsp());
+        assert_eq!(flags.base(), 0xfee0_0000);
+        assert_eq!(flags.mode(), Ok(ApicMode::XApic));
+        assert!(flags.with_base(0xfee0_0010).is_err());
+        assert_eq!(flags.with_base(0xfec0_0000).map(|flags| flags.base()), Ok(0xfec0_0000));
+    }
+
+    #[test]
+    pub fn test_transitions() {
+        let disabled = ApicBaseFlags::from_bits_truncate(0xfee0_0000);
+        assert_eq!(disabled.transition(ApicMode::X2Apic),
+            Err(ApicModeError::IllegalTransition { from: ApicMode::Disabled, to: ApicMode::X2Apic }));
+
+        let xapic = disabled.transition(ApicMode::XApic).expect("xapic");
+        let x2apic = xapic.transition(ApicMode::X2Apic).expect("x2apic");
+        assert_eq!(x2apic.bits(), 0xfee0_0c00);
+        assert!(x2apic.transition(ApicMode::XApic).is_err());
+        assert_eq!(x2apic.transition(ApicMode::Disabled).map(|flags| flags.bits()), Ok(0xfee0_0000));
+    }
+
+    #[test]
+    pub fn test_set_mode() {
+        let msr = FakeMsr::with(ApicBaseRegister::MSR, 0xfee0_0900);
+
+        unsafe {
+            assert!(ApicBaseRegister.set_mode(&msr, ApicMode::X2Apic).is_ok());
+            assert_eq!(msr.value(ApicBaseRegister::MSR), 0xfee0_0d00);
+
+            msr.set(ApicBaseRegister::MSR, 0xfee0_0500);
+            assert!(ApicBaseRegister.read(&msr).is_err());
+            assert!(ApicBaseRegister.set_mode(&msr, ApicMode::Disabled).is_err());
+        }
+    }
+}
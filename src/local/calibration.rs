@@ -0,0 +1,356 @@
+use core::convert::TryFrom;
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, WritableLocalApicRegister, LvtFlags, LvtMask, LvtTimerMode,
+    LvtTimerRegister, LvtTimerDivideValue, TimerDivideConfigurationFlags, LvtTimerDivideConfigurationRegister,
+    LvtTimerInitialCount, LvtTimerInitialCountRegister, LvtTimerCurrentCountRegister, TimerFrequency,
+};
+
+pub const MAX_CALIBRATION_SAMPLES: usize = 64;
+
+const DIVIDERS: [LvtTimerDivideValue; 8] = [
+    LvtTimerDivideValue(1),
+    LvtTimerDivideValue(2),
+    LvtTimerDivideValue(4),
+    LvtTimerDivideValue(8),
+    LvtTimerDivideValue(16),
+    LvtTimerDivideValue(32),
+    LvtTimerDivideValue(64),
+    LvtTimerDivideValue(128),
+];
+
+pub trait ReferenceClock {
+    fn frequency(&self) -> u64;
+    fn now(&self) -> u64;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CalibrationError {
+    InvalidReferenceClock,
+    InvalidWindow,
+    TooManySamples(usize),
+    NoSamples,
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for CalibrationError {
+    fn from(error: RegisterError) -> Self {
+        CalibrationError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CalibrationResult {
+    pub frequency: TimerFrequency,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub deviation_ppm: u32,
+}
+
+impl CalibrationResult {
+    /// Confidence in the estimate as a percentage from 0 to 100, weighing the
+    /// share of accepted samples against their spread around the mean.
+    pub fn confidence(&self) -> u32 {
+        let total = ((self.accepted + self.rejected) as u64).max(1);
+        let accepted = self.accepted as u64 * 1_000_000 / total;
+        let spread = 1_000_000u64.saturating_sub(self.deviation_ppm as u64);
+
+        (accepted * spread / 10_000_000_000) as u32
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub window: u64,
+    pub samples_per_divider: usize,
+    pub tolerance_ppm: u32,
+}
+
+impl Calibration {
+    pub fn new(window: u64) -> Self {
+        Calibration {
+            window,
+            samples_per_divider: 4,
+            tolerance_ppm: 10_000,
+        }
+    }
+
+    pub fn with_samples_per_divider(mut self, samples_per_divider: usize) -> Self {
+        self.samples_per_divider = samples_per_divider;
+        self
+    }
+
+    pub fn with_tolerance_ppm(mut self, tolerance_ppm: u32) -> Self {
+        self.tolerance_ppm = tolerance_ppm;
+        self
+    }
+
+    pub unsafe fn run(&self, apic: &dyn LocalApic, clock: &dyn ReferenceClock) -> Result<CalibrationResult, CalibrationError> {
+        if clock.frequency() == 0 {
+            return Err(CalibrationError::InvalidReferenceClock);
+        }
+        if self.window == 0 {
+            return Err(CalibrationError::InvalidWindow);
+        }
+
+        let total = self.samples_per_divider * DIVIDERS.len();
+        if total > MAX_CALIBRATION_SAMPLES {
+            return Err(CalibrationError::TooManySamples(total));
+        }
+
+        let lvt = LvtTimerRegister.read(apic)?;
+        let divide_configuration = LvtTimerDivideConfigurationRegister.read(apic)?;
+        LvtTimerRegister.write(apic, (lvt - LvtFlags::TIMER_MODE_2_BIT)
+            | LvtFlags::from(LvtTimerMode::OneShot)
+            | LvtFlags::from(LvtMask::Masked))?;
+
+        // put the timer back even when sampling fails partway through
+        let mut samples = [0u64; MAX_CALIBRATION_SAMPLES];
+        let collected = self.collect(apic, clock, &mut samples);
+        let restored = Self::restore(apic, lvt, divide_configuration);
+        let count = collected?;
+        restored?;
+
+        Self::estimate(&mut samples[..count], total, self.tolerance_ppm)
+    }
+
+    unsafe fn collect(&self, apic: &dyn LocalApic, clock: &dyn ReferenceClock, samples: &mut [u64])
+        -> Result<usize, CalibrationError> {
+        let mut count = 0;
+        for divider in DIVIDERS.iter() {
+            let flags = TimerDivideConfigurationFlags::try_from(*divider)
+                .map_err(|_| RegisterError::ReservedValue { field: "divide value", value: divider.0 })?;
+            LvtTimerDivideConfigurationRegister.write(apic, flags)?;
+
+            for _ in 0..self.samples_per_divider {
+                if let Some(sample) = self.sample(apic, clock, *divider)? {
+                    samples[count] = sample;
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    unsafe fn restore(apic: &dyn LocalApic, lvt: LvtFlags, divide_configuration: TimerDivideConfigurationFlags)
+        -> Result<(), CalibrationError> {
+        LvtTimerInitialCountRegister.write(apic, LvtTimerInitialCount(0))?;
+        LvtTimerDivideConfigurationRegister.write(apic, divide_configuration)?;
+        LvtTimerRegister.write(apic, lvt)?;
+        Ok(())
+    }
+
+    unsafe fn sample(&self, apic: &dyn LocalApic, clock: &dyn ReferenceClock, divider: LvtTimerDivideValue)
+        -> Result<Option<u64>, CalibrationError> {
+        LvtTimerInitialCountRegister.write(apic, LvtTimerInitialCount(u32::MAX))?;
+
+        let start = clock.now();
+        let start_count = LvtTimerCurrentCountRegister.read(apic)?.0;
+
+        let mut end = clock.now();
+        while end.wrapping_sub(start) < self.window {
+            end = clock.now();
+        }
+        let end_count = LvtTimerCurrentCountRegister.read(apic)?.0;
+
+        // the counter ran out or was reloaded, so the elapsed tick count is unknown
+        let elapsed_count = match start_count.checked_sub(end_count) {
+            Some(count) if end_count != 0 => count,
+            _ => return Ok(None),
+        };
+
+        let ticks = elapsed_count as u128 * divider.0 as u128;
+        let elapsed = end.wrapping_sub(start) as u128;
+
+        Ok(Some((ticks * clock.frequency() as u128 / elapsed) as u64))
+    }
+
+    fn estimate(samples: &mut [u64], total: usize, tolerance_ppm: u32) -> Result<CalibrationResult, CalibrationError> {
+        if samples.is_empty() {
+            return Err(CalibrationError::NoSamples);
+        }
+
+        samples.sort_unstable();
+        let median = samples[samples.len() / 2];
+        let tolerance = (median as u128 * tolerance_ppm as u128 / 1_000_000) as u64;
+
+        let accepted = || samples.iter().filter(|sample| sample.abs_diff(median) <= tolerance);
+        let count = accepted().count();
+        let mean = (accepted().map(|sample| *sample as u128).sum::<u128>() / count as u128) as u64;
+        let deviation = accepted().map(|sample| sample.abs_diff(mean) as u128).sum::<u128>() / count as u128;
+
+        Ok(CalibrationResult {
+            frequency: TimerFrequency(mean),
+            accepted: count,
+            rejected: total - count,
+            deviation_ppm: (deviation * 1_000_000 / mean.max(1) as u128) as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::local::{EmulatedLocalApic, InterruptVector, LocalApicRegisterIndex};
+
+    struct FakeClock<'a> {
+        apic: &'a EmulatedLocalApic,
+        now: Cell<u64>,
+        step: u64,
+        bus_clocks_per_step: u64,
+        glitch_every: u64,
+        calls: Cell<u64>,
+    }
+
+    impl<'a> ReferenceClock for FakeClock<'a> {
+        fn frequency(&self) -> u64 {
+            1_000_000
+        }
+
+        // modulo rather than is_multiple_of, which needs rust 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        fn now(&self) -> u64 {
+            let calls = self.calls.get() + 1;
+            self.calls.set(calls);
+
+            let mut bus_clocks = self.bus_clocks_per_step;
+            if self.glitch_every != 0 && calls % self.glitch_every == 0 {
+                bus_clocks *= 50;
+            }
+
+            self.apic.advance_timer(bus_clocks);
+            self.now.set(self.now.get() + self.step);
+            self.now.get()
+        }
+    }
+
+    fn clock(apic: &EmulatedLocalApic, step: u64, glitch_every: u64) -> FakeClock<'_> {
+        FakeClock {
+            apic,
+            now: Cell::new(0),
+            step,
+            bus_clocks_per_step: step * 100,
+            glitch_every,
+            calls: Cell::new(0),
+        }
+    }
+
+    #[test]
+    pub fn test_calibrate() {
+        let apic = EmulatedLocalApic::new(0);
+        let clock = clock(&apic, 10, 0);
+
+        unsafe {
+            LvtTimerRegister.write(&apic, LvtFlags::from_bits_truncate(0x30)).unwrap();
+            let result = Calibration::new(1_000).run(&apic, &clock).unwrap();
+
+            assert!(result.frequency.0.abs_diff(100_000_000) < 100_000);
+            assert_eq!(result.accepted, 32);
+            assert_eq!(result.rejected, 0);
+            assert!(result.confidence() >= 99);
+
+            assert_eq!(LvtTimerRegister.read(&apic).unwrap().vector(), InterruptVector(0x30));
+            assert_eq!(LvtTimerCurrentCountRegister.read(&apic).unwrap().0, 0);
+        }
+    }
+
+    #[test]
+    pub fn test_rejects_outliers() {
+        let apic = EmulatedLocalApic::new(0);
+        let clock = clock(&apic, 10, 500);
+
+        unsafe {
+            let result = Calibration::new(1_000).run(&apic, &clock).unwrap();
+
+            assert!(result.frequency.0.abs_diff(100_000_000) < 100_000);
+            assert!(result.rejected > 0);
+            assert!(result.confidence() < 100);
+        }
+    }
+
+    struct ReloadingApic(Cell<u32>);
+
+    impl LocalApic for ReloadingApic {
+        unsafe fn read_reg_32(&self, _index: LocalApicRegisterIndex) -> u32 {
+            self.0.set(self.0.get() + 1);
+            self.0.get()
+        }
+
+        unsafe fn write_reg_32(&self, _index: LocalApicRegisterIndex, _value: u32) {}
+    }
+
+    #[test]
+    pub fn test_reloaded_counter_is_discarded() {
+        let apic = ReloadingApic(Cell::new(0));
+        let emulated = EmulatedLocalApic::new(0);
+        let clock = clock(&emulated, 10, 0);
+
+        unsafe {
+            assert_eq!(Calibration::new(1_000).sample(&apic, &clock, LvtTimerDivideValue(1)), Ok(None));
+        }
+    }
+
+    struct FailingApic<'a> {
+        apic: &'a EmulatedLocalApic,
+        count_reads: Cell<u32>,
+    }
+
+    impl<'a> LocalApic for FailingApic<'a> {
+        unsafe fn read_reg_32(&self, index: LocalApicRegisterIndex) -> u32 {
+            self.apic.read_reg_32(index)
+        }
+
+        unsafe fn write_reg_32(&self, index: LocalApicRegisterIndex, value: u32) {
+            self.apic.write_reg_32(index, value)
+        }
+
+        // the current count goes away after a few samples
+        fn supports(&self, index: LocalApicRegisterIndex) -> bool {
+            if index != LocalApicRegisterIndex::TimerCurrentCount {
+                return true;
+            }
+
+            self.count_reads.set(self.count_reads.get() + 1);
+            self.count_reads.get() <= 10
+        }
+    }
+
+    #[test]
+    pub fn test_failure_restores_timer() {
+        let emulated = EmulatedLocalApic::new(0);
+        let apic = FailingApic { apic: &emulated, count_reads: Cell::new(0) };
+        let clock = clock(&emulated, 10, 0);
+
+        unsafe {
+            let lvt = LvtFlags::from_bits_truncate(0x30) | LvtFlags::from(LvtTimerMode::Periodic);
+            let divide_configuration = TimerDivideConfigurationFlags::try_from(LvtTimerDivideValue(16)).unwrap();
+            LvtTimerRegister.write(&emulated, lvt).unwrap();
+            LvtTimerDivideConfigurationRegister.write(&emulated, divide_configuration).unwrap();
+
+            assert_eq!(Calibration::new(1_000).run(&apic, &clock),
+                Err(CalibrationError::Register(RegisterError::Unsupported(LocalApicRegisterIndex::TimerCurrentCount))));
+
+            assert_eq!(LvtTimerRegister.read(&emulated), Ok(lvt));
+            assert_eq!(LvtTimerDivideConfigurationRegister.read(&emulated), Ok(divide_configuration));
+            assert_eq!(LvtTimerCurrentCountRegister.read(&emulated).unwrap().0, 0);
+        }
+    }
+
+    #[test]
+    pub fn test_invalid_parameters() {
+        let apic = EmulatedLocalApic::new(0);
+        let coarse = clock(&apic, 10_000_000, 0);
+        let clock = clock(&apic, 10, 0);
+
+        unsafe {
+            assert_eq!(Calibration::new(0).run(&apic, &clock), Err(CalibrationError::InvalidWindow));
+            assert_eq!(Calibration::new(1_000).with_samples_per_divider(9).run(&apic, &clock),
+                Err(CalibrationError::TooManySamples(72)));
+            assert_eq!(Calibration::new(10_000_000_000).with_samples_per_divider(1).run(&apic, &coarse),
+                Err(CalibrationError::NoSamples));
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct DestinationFormatFlags: u32 {
@@ -62,13 +63,17 @@ impl DestinationFormatModel {
 pub struct DestinationFormatRegister;
 impl LocalApicRegister for DestinationFormatRegister {
     type Value = DestinationFormatFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        DestinationFormatFlags::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::DestinationFormat))
+impl ReadableLocalApicRegister for DestinationFormatRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(DestinationFormatFlags::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::DestinationFormat)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::DestinationFormat, DestinationFormatFlags::from(value).bits());
+impl WritableLocalApicRegister for DestinationFormatRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::DestinationFormat, value.bits())
     }
 }
 
@@ -0,0 +1,437 @@
+use core::cell::RefCell;
+use crate::local::{
+    LocalApic, LocalApicRegisterIndex, InterruptVector, InterruptVectorSet, ErrorStatusFlags,
+    InterruptCommandFlags, LvtFlags, LvtTimerMode, LvtTimerDivideValue, TimerDivideConfigurationFlags,
+    SivrFlags, TaskPriorityFlags, LvtTriggerMode, LvtMask, IcrDeliveryMode,
+    IcrDestinationShorthand, IcrTriggerMode, PrioritySnapshot,
+};
+
+const LVT_COUNT: usize = 7;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct State {
+    id: u32,
+    version: u32,
+    task_priority: u32,
+    logical_destination: u32,
+    destination_format: u32,
+    spurious_interrupt: u32,
+    in_service: InterruptVectorSet,
+    trigger_mode: InterruptVectorSet,
+    interrupt_request: InterruptVectorSet,
+    error_status: ErrorStatusFlags,
+    pending_errors: ErrorStatusFlags,
+    interrupt_command: u64,
+    last_ipi: Option<InterruptCommandFlags>,
+    lvt: [u32; LVT_COUNT],
+    timer_initial_count: u32,
+    timer_current_count: u32,
+    timer_divide_configuration: u32,
+    timer_remainder: u64,
+}
+
+pub struct EmulatedLocalApic {
+    state: RefCell<State>,
+}
+
+impl EmulatedLocalApic {
+    pub fn new(id: u8) -> Self {
+        EmulatedLocalApic {
+            state: RefCell::new(State {
+                id: (id as u32) << 24,
+                version: 0x0105_0014,
+                task_priority: 0,
+                logical_destination: 0,
+                destination_format: 0xffff_ffff,
+                spurious_interrupt: 0xff,
+                in_service: InterruptVectorSet::new(),
+                trigger_mode: InterruptVectorSet::new(),
+                interrupt_request: InterruptVectorSet::new(),
+                error_status: ErrorStatusFlags::empty(),
+                pending_errors: ErrorStatusFlags::empty(),
+                interrupt_command: 0,
+                last_ipi: None,
+                lvt: [LvtFlags::MASK.bits(); LVT_COUNT],
+                timer_initial_count: 0,
+                timer_current_count: 0,
+                timer_divide_configuration: 0,
+                timer_remainder: 0,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        SivrFlags::from_bits_truncate(self.state.borrow().spurious_interrupt).is_enabled()
+    }
+
+    pub fn interrupt_request(&self) -> InterruptVectorSet {
+        self.state.borrow().interrupt_request
+    }
+
+    pub fn in_service(&self) -> InterruptVectorSet {
+        self.state.borrow().in_service
+    }
+
+    pub fn trigger_mode(&self) -> InterruptVectorSet {
+        self.state.borrow().trigger_mode
+    }
+
+    pub fn pending_errors(&self) -> ErrorStatusFlags {
+        self.state.borrow().pending_errors
+    }
+
+    pub fn last_ipi(&self) -> Option<InterruptCommandFlags> {
+        self.state.borrow().last_ipi
+    }
+
+    pub fn processor_priority(&self) -> u32 {
+        self.state.borrow().processor_priority()
+    }
+
+    pub fn request(&self, vector: InterruptVector, trigger_mode: LvtTriggerMode) {
+        self.state.borrow_mut().request(vector, trigger_mode);
+    }
+
+    pub fn accept(&self) -> Option<InterruptVector> {
+        let mut state = self.state.borrow_mut();
+        if !SivrFlags::from_bits_truncate(state.spurious_interrupt).is_enabled() {
+            return None;
+        }
+
+        let vector = state.snapshot().next_delivery()?;
+
+        state.interrupt_request.remove(vector);
+        state.in_service.insert(vector);
+        Some(vector)
+    }
+
+    pub fn advance_timer(&self, bus_clocks: u64) {
+        self.state.borrow_mut().advance_timer(bus_clocks);
+    }
+}
+
+impl State {
+    fn snapshot(&self) -> PrioritySnapshot {
+        PrioritySnapshot {
+            task_priority: TaskPriorityFlags::from_bits_truncate(self.task_priority),
+            in_service: self.in_service,
+            interrupt_request: self.interrupt_request,
+        }
+    }
+
+    fn processor_priority(&self) -> u32 {
+        self.snapshot().processor_priority().bits()
+    }
+
+    fn request(&mut self, vector: InterruptVector, trigger_mode: LvtTriggerMode) {
+        if vector.0 < 16 || vector.0 > 0xff {
+            self.pending_errors |= ErrorStatusFlags::RECEIVED_ILLEGAL_VECTOR;
+            return;
+        }
+
+        self.interrupt_request.insert(vector);
+        match trigger_mode {
+            LvtTriggerMode::Level => self.trigger_mode.insert(vector),
+            LvtTriggerMode::Edge => self.trigger_mode.remove(vector),
+        }
+    }
+
+    fn end_of_interrupt(&mut self) {
+        if let Some(vector) = self.in_service.highest() {
+            self.in_service.remove(vector);
+        }
+    }
+
+    fn send_ipi(&mut self, command: InterruptCommandFlags) {
+        self.last_ipi = Some(command);
+
+        let vector = command.vector();
+        if command.delivery_mode() == Ok(IcrDeliveryMode::Fixed) && vector.0 < 16 {
+            self.pending_errors |= ErrorStatusFlags::SEND_ILLEGAL_VECTOR;
+            return;
+        }
+
+        // self and all-including-self shorthands loop back to this apic
+        match command.destination_shorthand() {
+            Ok(IcrDestinationShorthand::ToSelf) | Ok(IcrDestinationShorthand::AllIncludingSelf) => {
+                let trigger_mode = (command.trigger_mode() == IcrTriggerMode::Level).into();
+                self.request(vector, trigger_mode);
+            }
+            _ => {}
+        }
+    }
+
+    fn write_timer_initial_count(&mut self, value: u32) {
+        self.timer_initial_count = value;
+        self.timer_current_count = value;
+        self.timer_remainder = 0;
+    }
+
+    fn advance_timer(&mut self, bus_clocks: u64) {
+        if self.timer_current_count == 0 {
+            return;
+        }
+
+        let divisor = LvtTimerDivideValue::from(
+            TimerDivideConfigurationFlags::from_bits_truncate(self.timer_divide_configuration)).0 as u64;
+        let ticks = (self.timer_remainder + bus_clocks) / divisor;
+        self.timer_remainder = (self.timer_remainder + bus_clocks) % divisor;
+
+        let lvt = LvtFlags::from_bits_truncate(self.lvt[0]);
+        // the reserved mode 0b11 is not decodable, so it runs as one-shot
+        let mode = lvt.timer_mode_2_bit().unwrap_or(LvtTimerMode::OneShot);
+        let mut ticks = ticks;
+        while ticks >= self.timer_current_count as u64 && self.timer_current_count != 0 {
+            ticks -= self.timer_current_count as u64;
+
+            if lvt.mask() == LvtMask::NotMasked {
+                self.request(lvt.vector(), LvtTriggerMode::Edge);
+            }
+
+            if mode == LvtTimerMode::Periodic {
+                self.timer_current_count = self.timer_initial_count;
+            } else {
+                self.timer_current_count = 0;
+            }
+        }
+
+        if self.timer_current_count != 0 {
+            self.timer_current_count -= ticks as u32;
+        }
+    }
+
+    fn lvt_slot(index: LocalApicRegisterIndex) -> Option<usize> {
+        match index {
+            LocalApicRegisterIndex::LvtTimer => Some(0),
+            LocalApicRegisterIndex::LvtCmci => Some(1),
+            LocalApicRegisterIndex::LvtThermalSensor => Some(2),
+            LocalApicRegisterIndex::LvtPerfCounters => Some(3),
+            LocalApicRegisterIndex::LvtLINT0 => Some(4),
+            LocalApicRegisterIndex::LvtLINT1 => Some(5),
+            LocalApicRegisterIndex::LvtError => Some(6),
+            _ => None,
+        }
+    }
+
+    fn word(set: &InterruptVectorSet, indexes: &[LocalApicRegisterIndex; 8], index: LocalApicRegisterIndex) -> Option<u32> {
+        indexes.iter().position(|i| *i == index).map(|n| set.words()[n])
+    }
+
+    fn read(&mut self, index: LocalApicRegisterIndex) -> u32 {
+        if !index.is_readable() {
+            self.pending_errors |= ErrorStatusFlags::ILLEGAL_REGISTER_ADDRESS;
+            return 0;
+        }
+
+        if let Some(slot) = Self::lvt_slot(index) {
+            return self.lvt[slot];
+        }
+
+        if let Some(word) = Self::word(&self.in_service, &LocalApicRegisterIndex::IN_SERVICE, index)
+            .or_else(|| Self::word(&self.trigger_mode, &LocalApicRegisterIndex::TRIGGER_MODE, index))
+            .or_else(|| Self::word(&self.interrupt_request, &LocalApicRegisterIndex::INTERRUPT_REQUEST, index)) {
+            return word;
+        }
+
+        match index {
+            LocalApicRegisterIndex::Id => self.id,
+            LocalApicRegisterIndex::Version => self.version,
+            LocalApicRegisterIndex::TaskPriority => self.task_priority,
+            LocalApicRegisterIndex::ArbitrationPriority => self.snapshot().arbitration_priority().bits(),
+            LocalApicRegisterIndex::ProcessorPriority => self.processor_priority(),
+            LocalApicRegisterIndex::LogicalDestination => self.logical_destination,
+            LocalApicRegisterIndex::DestinationFormat => self.destination_format,
+            LocalApicRegisterIndex::SpuriousInterrupt => self.spurious_interrupt,
+            LocalApicRegisterIndex::ErrorStatus => self.error_status.bits(),
+            LocalApicRegisterIndex::InterruptCommand0 => self.interrupt_command as u32,
+            LocalApicRegisterIndex::InterruptCommand1 => (self.interrupt_command >> 32) as u32,
+            LocalApicRegisterIndex::TimerInitialCount => self.timer_initial_count,
+            LocalApicRegisterIndex::TimerCurrentCount => self.timer_current_count,
+            LocalApicRegisterIndex::TimerDivideConfiguration => self.timer_divide_configuration,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, index: LocalApicRegisterIndex, value: u32) {
+        if !index.is_writable() {
+            self.pending_errors |= ErrorStatusFlags::ILLEGAL_REGISTER_ADDRESS;
+            return;
+        }
+
+        if let Some(slot) = Self::lvt_slot(index) {
+            self.lvt[slot] = value;
+            return;
+        }
+
+        match index {
+            LocalApicRegisterIndex::Id => self.id = value & 0xff00_0000,
+            LocalApicRegisterIndex::TaskPriority => self.task_priority = value & 0xff,
+            LocalApicRegisterIndex::EndOfInterrupt => self.end_of_interrupt(),
+            LocalApicRegisterIndex::LogicalDestination => self.logical_destination = value & 0xff00_0000,
+            LocalApicRegisterIndex::DestinationFormat => self.destination_format = value | 0x0fff_ffff,
+            LocalApicRegisterIndex::SpuriousInterrupt => self.spurious_interrupt = value,
+            LocalApicRegisterIndex::ErrorStatus => {
+                self.error_status = self.pending_errors;
+                self.pending_errors = ErrorStatusFlags::empty();
+            }
+            LocalApicRegisterIndex::InterruptCommand1 => {
+                self.interrupt_command = (self.interrupt_command & 0xffff_ffff) | ((value as u64) << 32);
+            }
+            LocalApicRegisterIndex::InterruptCommand0 => {
+                self.interrupt_command = (self.interrupt_command & 0xffff_ffff_0000_0000) | value as u64;
+                self.send_ipi(InterruptCommandFlags::from_bits_truncate(self.interrupt_command));
+            }
+            LocalApicRegisterIndex::TimerInitialCount => self.write_timer_initial_count(value),
+            LocalApicRegisterIndex::TimerDivideConfiguration => self.timer_divide_configuration = value & 0xb,
+            // self ipi only exists in x2apic mode
+            _ => self.pending_errors |= ErrorStatusFlags::ILLEGAL_REGISTER_ADDRESS,
+        }
+    }
+}
+
+impl LocalApic for EmulatedLocalApic {
+    unsafe fn read_reg_32(&self, index: LocalApicRegisterIndex) -> u32 {
+        self.state.borrow_mut().read(index)
+    }
+
+    unsafe fn write_reg_32(&self, index: LocalApicRegisterIndex, value: u32) {
+        self.state.borrow_mut().write(index, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RegisterError;
+    use crate::local::*;
+
+    #[test]
+    pub fn test_registers_round_trip() {
+        let apic = EmulatedLocalApic::new(3);
+
+        unsafe {
+            assert_eq!(Id8BitRegister.read(&apic).unwrap(), ApicId::Id8Bit(3));
+            assert_eq!(VersionRegister.read(&apic).unwrap().lvt_entries(), 6);
+
+            TaskPriorityRegister.write(&apic, TaskPriorityFlags::from(PriorityClass(4))).unwrap();
+            assert_eq!(TaskPriorityRegister.read(&apic).unwrap().priority_class(), PriorityClass(4));
+
+            LvtTimerInitialCountRegister.write(&apic, LvtTimerInitialCount(1000)).unwrap();
+            assert_eq!(LvtTimerCurrentCountRegister.read(&apic).unwrap(), LvtTimerCurrentCount(1000));
+        }
+    }
+
+    #[test]
+    pub fn test_accept_and_eoi() {
+        let apic = EmulatedLocalApic::new(0);
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::APIC_ENABLE | SivrFlags::VECTOR).unwrap();
+        }
+
+        apic.request(InterruptVector(0x31), LvtTriggerMode::Edge);
+        apic.request(InterruptVector(0x52), LvtTriggerMode::Level);
+
+        assert_eq!(apic.accept(), Some(InterruptVector(0x52)));
+        assert!(apic.trigger_mode().contains(InterruptVector(0x52)));
+        assert_eq!(apic.processor_priority(), 0x50);
+
+        // lower class is held off by the in-service vector
+        assert_eq!(apic.accept(), None);
+
+        unsafe {
+            assert!(InServiceRegister.read(&apic).unwrap().contains(InterruptVector(0x52)));
+            EoiRegister.write(&apic, Eoi(0)).unwrap();
+        }
+
+        assert_eq!(apic.processor_priority(), 0x00);
+        assert_eq!(apic.accept(), Some(InterruptVector(0x31)));
+    }
+
+    #[test]
+    pub fn test_task_priority_masks_delivery() {
+        let apic = EmulatedLocalApic::new(0);
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::APIC_ENABLE | SivrFlags::VECTOR).unwrap();
+            TaskPriorityRegister.write(&apic, TaskPriorityFlags::from(PriorityClass(5))).unwrap();
+        }
+
+        apic.request(InterruptVector(0x50), LvtTriggerMode::Edge);
+        assert_eq!(apic.accept(), None);
+
+        apic.request(InterruptVector(0x60), LvtTriggerMode::Edge);
+        assert_eq!(apic.accept(), Some(InterruptVector(0x60)));
+    }
+
+    #[test]
+    pub fn test_illegal_access_reported() {
+        let apic = EmulatedLocalApic::new(0);
+
+        unsafe {
+            apic.write_reg_32(LocalApicRegisterIndex::Version, 0);
+            apic.read_reg_32(LocalApicRegisterIndex::EndOfInterrupt);
+            apic.request(InterruptVector(0x2), LvtTriggerMode::Edge);
+
+            assert_eq!(ErrorStatusRegister.read(&apic).unwrap(), ErrorStatusFlags::empty());
+            ErrorStatusRegister.write(&apic, ErrorStatusFlags::empty()).unwrap();
+            assert_eq!(ErrorStatusRegister.read(&apic).unwrap(),
+                ErrorStatusFlags::ILLEGAL_REGISTER_ADDRESS | ErrorStatusFlags::RECEIVED_ILLEGAL_VECTOR);
+        }
+    }
+
+    #[test]
+    pub fn test_self_ipi_loops_back() {
+        let apic = EmulatedLocalApic::new(0);
+        let ipi = Ipi::fixed(InterruptVector(0x70), IpiDestination::Shorthand(IcrDestinationShorthand::ToSelf));
+
+        unsafe {
+            InterruptCommandRegister.write(&apic, ipi.to_flags().expect("flags")).unwrap();
+        }
+        assert!(apic.interrupt_request().contains(InterruptVector(0x70)));
+        assert_eq!(apic.last_ipi().map(|command| command.vector()), Some(InterruptVector(0x70)));
+    }
+
+    #[test]
+    pub fn test_reserved_timer_mode_is_an_error() {
+        let apic = EmulatedLocalApic::new(0);
+
+        unsafe {
+            LvtTimerRegister.write(&apic, LvtFlags::TIMER_MODE_2_BIT).unwrap();
+            assert_eq!(LvtTimerRegister.read(&apic),
+                Err(RegisterError::ReservedValue { field: "timer mode", value: 0x3 }));
+        }
+    }
+
+    #[test]
+    pub fn test_reserved_timer_mode_runs_one_shot() {
+        let apic = EmulatedLocalApic::new(0);
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::APIC_ENABLE | SivrFlags::VECTOR).unwrap();
+            LvtTimerRegister.write(&apic, LvtFlags::from_bits_truncate(0x40) | LvtFlags::TIMER_MODE_2_BIT).unwrap();
+            LvtTimerDivideConfigurationRegister.write(&apic, TimerDivideConfigurationFlags::from_bits_truncate(0xb)).unwrap();
+            LvtTimerInitialCountRegister.write(&apic, LvtTimerInitialCount(100)).unwrap();
+        }
+
+        apic.advance_timer(250);
+        assert!(apic.interrupt_request().contains(InterruptVector(0x40)));
+        unsafe {
+            assert_eq!(LvtTimerCurrentCountRegister.read(&apic).unwrap(), LvtTimerCurrentCount(0));
+        }
+    }
+
+    #[test]
+    pub fn test_periodic_timer() {
+        let apic = EmulatedLocalApic::new(0);
+        unsafe {
+            SpuriousInterruptVectorRegister.write(&apic, SivrFlags::APIC_ENABLE | SivrFlags::VECTOR).unwrap();
+            LvtTimerRegister.write(&apic, LvtFlags::from_bits_truncate(0x40) | LvtFlags::from(LvtTimerMode::Periodic)).unwrap();
+            LvtTimerDivideConfigurationRegister.write(&apic, TimerDivideConfigurationFlags::from_bits_truncate(0xb)).unwrap();
+            LvtTimerInitialCountRegister.write(&apic, LvtTimerInitialCount(100)).unwrap();
+        }
+
+        apic.advance_timer(250);
+        assert!(apic.interrupt_request().contains(InterruptVector(0x40)));
+        unsafe {
+            assert_eq!(LvtTimerCurrentCountRegister.read(&apic).unwrap(), LvtTimerCurrentCount(50));
+        }
+    }
+}
@@ -1,16 +1,15 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Eoi(pub u32);
 pub struct EoiRegister;
 impl LocalApicRegister for EoiRegister {
     type Value = Eoi;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Eoi(apic.read_reg_32(LocalApicRegisterIndex::EndOfInterrupt)).into()
-    }
-
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::EndOfInterrupt, value.0);
+impl WritableLocalApicRegister for EoiRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::EndOfInterrupt, value.0)
     }
 }
\ No newline at end of file
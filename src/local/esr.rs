@@ -1,4 +1,5 @@
-use super::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use super::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct ErrorStatusFlags: u32 {
@@ -42,12 +43,16 @@ impl From<ErrorStatus> for ErrorStatusFlags {
 pub struct ErrorStatusRegister;
 impl LocalApicRegister for ErrorStatusRegister {
     type Value = ErrorStatusFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::ErrorStatus))
+impl ReadableLocalApicRegister for ErrorStatusRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::ErrorStatus)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::ErrorStatus, Self::Value::from(value).bits());
+impl WritableLocalApicRegister for ErrorStatusRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::ErrorStatus, value.bits())
     }
 }
\ No newline at end of file
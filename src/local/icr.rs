@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 use core::convert::TryFrom;
 use core::result::Result;
 use crate::local::InterruptVector;
@@ -23,21 +24,74 @@ bitflags! {
         const DESTINATION_SHORTHAND = 0xc0000;
         const RESERVED3 = 0x00ffffff_fff00000;
         const DESTINATION = 0xff000000_00000000;
+        const X2APIC_DESTINATION = 0xffffffff_00000000;
         const LOW_BITS = 0xffffffff;
         const HIGH_BITS = 0xffffffff_00000000;
     }
 }
 
 impl InterruptCommandFlags {
-    pub fn delivery_mode(&self) -> IcrDeliveryMode {
-        let bits = (*self & InterruptCommandFlags::DELIVERY_MODE).bits() >> 8;
-        IcrDeliveryMode::try_from(bits as u8).expect("icr delivery mode")
+    pub fn delivery_mode(&self) -> Result<IcrDeliveryMode, RegisterError> {
+        let value = ((*self & InterruptCommandFlags::DELIVERY_MODE).bits() >> 8) as u32;
+        IcrDeliveryMode::try_from(value as u8).map_err(|_| RegisterError::ReservedValue { field: "delivery mode", value })
     }
 
     pub fn vector(&self) -> InterruptVector {
         InterruptVector((*self & InterruptCommandFlags::VECTOR).bits() as u32)
     }
 
+    pub fn destination_mode(&self) -> IcrDestinationMode {
+        if self.contains(InterruptCommandFlags::DESTINATION_MODE) {
+            IcrDestinationMode::Logical
+        } else {
+            IcrDestinationMode::Physical
+        }
+    }
+
+    pub fn is_send_pending(&self) -> bool {
+        self.contains(InterruptCommandFlags::DELIVERY_STATUS)
+    }
+
+    pub fn level(&self) -> IcrLevel {
+        if self.contains(InterruptCommandFlags::LEVEL) {
+            IcrLevel::Assert
+        } else {
+            IcrLevel::Deassert
+        }
+    }
+
+    pub fn trigger_mode(&self) -> IcrTriggerMode {
+        if self.contains(InterruptCommandFlags::TRIGGER_MODE) {
+            IcrTriggerMode::Level
+        } else {
+            IcrTriggerMode::Edge
+        }
+    }
+
+    pub fn destination_shorthand(&self) -> Result<IcrDestinationShorthand, RegisterError> {
+        let value = ((*self & InterruptCommandFlags::DESTINATION_SHORTHAND).bits() >> 18) as u32;
+        IcrDestinationShorthand::try_from(value as u8)
+            .map_err(|_| RegisterError::ReservedValue { field: "destination shorthand", value })
+    }
+
+    pub fn destination(&self) -> u32 {
+        ((*self & InterruptCommandFlags::DESTINATION).bits() >> 56) as u32
+    }
+
+    pub fn x2apic_destination(&self) -> u32 {
+        ((*self & InterruptCommandFlags::X2APIC_DESTINATION).bits() >> 32) as u32
+    }
+
+    pub fn with_destination(self, destination: u32) -> Self {
+        (self - InterruptCommandFlags::DESTINATION)
+            | Self::from_bits_truncate(((destination & 0xff) as u64) << 56)
+    }
+
+    pub fn with_x2apic_destination(self, destination: u32) -> Self {
+        (self - InterruptCommandFlags::X2APIC_DESTINATION)
+            | Self::from_bits_truncate((destination as u64) << 32)
+    }
+
     pub fn low_word(&self) -> u32 {
         self.bits() as u32
     }
@@ -92,22 +146,306 @@ impl TryFrom<u8> for IcrDeliveryMode {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum IcrDestinationMode {
+    Physical = 0x0,
+    Logical,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum IcrLevel {
+    Deassert = 0x0,
+    Assert,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum IcrTriggerMode {
+    Edge = 0x0,
+    Level,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum IcrDestinationShorthand {
+    NoShorthand = 0x0,
+    ToSelf,
+    AllIncludingSelf,
+    AllExcludingSelf,
+}
+
+impl IcrDestinationShorthand {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.as_u8() as u64
+    }
+}
+
+impl TryFrom<u8> for IcrDestinationShorthand {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(IcrDestinationShorthand::NoShorthand),
+            0x1 => Ok(IcrDestinationShorthand::ToSelf),
+            0x2 => Ok(IcrDestinationShorthand::AllIncludingSelf),
+            0x3 => Ok(IcrDestinationShorthand::AllExcludingSelf),
+            _ => Err("invalid icr destination shorthand")
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IpiDestination {
+    Physical(u32),
+    Logical(u32),
+    Shorthand(IcrDestinationShorthand),
+}
+
+impl IpiDestination {
+    pub fn shorthand(&self) -> IcrDestinationShorthand {
+        match *self {
+            IpiDestination::Shorthand(shorthand) => shorthand,
+            _ => IcrDestinationShorthand::NoShorthand,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ipi {
+    pub delivery_mode: IcrDeliveryMode,
+    pub vector: InterruptVector,
+    pub level: IcrLevel,
+    pub trigger_mode: IcrTriggerMode,
+    pub destination: IpiDestination,
+}
+
+impl Ipi {
+    pub fn fixed(vector: InterruptVector, destination: IpiDestination) -> Self {
+        Ipi {
+            delivery_mode: IcrDeliveryMode::Fixed,
+            vector,
+            level: IcrLevel::Assert,
+            trigger_mode: IcrTriggerMode::Edge,
+            destination,
+        }
+    }
+
+    pub fn lowest_priority(vector: InterruptVector, destination: IpiDestination) -> Self {
+        Ipi {
+            delivery_mode: IcrDeliveryMode::LowestPriority,
+            ..Ipi::fixed(vector, destination)
+        }
+    }
+
+    pub fn nmi(destination: IpiDestination) -> Self {
+        Ipi {
+            delivery_mode: IcrDeliveryMode::NMI,
+            ..Ipi::fixed(InterruptVector(0), destination)
+        }
+    }
+
+    pub fn init_assert(destination: IpiDestination) -> Self {
+        Ipi {
+            delivery_mode: IcrDeliveryMode::INIT,
+            vector: InterruptVector(0),
+            level: IcrLevel::Assert,
+            trigger_mode: IcrTriggerMode::Level,
+            destination,
+        }
+    }
+
+    pub fn init_deassert(destination: IpiDestination) -> Self {
+        Ipi {
+            level: IcrLevel::Deassert,
+            ..Ipi::init_assert(destination)
+        }
+    }
+
+    pub fn startup(page: u8, destination: IpiDestination) -> Self {
+        Ipi {
+            delivery_mode: IcrDeliveryMode::StartUp,
+            ..Ipi::fixed(InterruptVector(page as u32), destination)
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let shorthand = self.destination.shorthand();
+
+        match self.delivery_mode {
+            IcrDeliveryMode::Reserved | IcrDeliveryMode::Reserved2 => return Err("reserved icr delivery mode"),
+            IcrDeliveryMode::Fixed | IcrDeliveryMode::LowestPriority if self.vector.0 < 16 => {
+                return Err("illegal ipi vector")
+            }
+            _ => {}
+        }
+
+        if self.vector.0 > 0xff {
+            return Err("illegal ipi vector");
+        }
+
+        match shorthand {
+            IcrDestinationShorthand::ToSelf
+                if self.delivery_mode != IcrDeliveryMode::Fixed || self.trigger_mode != IcrTriggerMode::Edge => {
+                return Err("only edge triggered fixed ipis can be sent to self");
+            }
+            IcrDestinationShorthand::AllIncludingSelf if self.delivery_mode != IcrDeliveryMode::Fixed => {
+                return Err("only fixed ipis can be sent to all including self");
+            }
+            _ => {}
+        }
+
+        if self.trigger_mode == IcrTriggerMode::Level {
+            match self.delivery_mode {
+                IcrDeliveryMode::SMI | IcrDeliveryMode::StartUp => return Err("ipi delivery mode must be edge triggered"),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_flags(&self) -> Result<InterruptCommandFlags, &'static str> {
+        let destination = match self.destination {
+            IpiDestination::Physical(id) | IpiDestination::Logical(id) if id > 0xff => {
+                return Err("destination does not fit in xapic icr")
+            }
+            IpiDestination::Physical(id) | IpiDestination::Logical(id) => id,
+            IpiDestination::Shorthand(_) => 0,
+        };
+
+        Ok(self.command()?.with_destination(destination))
+    }
+
+    pub fn to_x2apic_flags(&self) -> Result<InterruptCommandFlags, &'static str> {
+        let destination = match self.destination {
+            IpiDestination::Physical(id) | IpiDestination::Logical(id) => id,
+            IpiDestination::Shorthand(_) => 0,
+        };
+
+        Ok(self.command()?.with_x2apic_destination(destination))
+    }
+
+    fn command(&self) -> Result<InterruptCommandFlags, &'static str> {
+        self.validate()?;
+
+        let mut flags = self.delivery_mode.as_flags()
+            | InterruptCommandFlags::from_bits_truncate(self.vector.0 as u64)
+            | InterruptCommandFlags::from_bits_truncate(self.destination.shorthand().as_u64() << 18);
+
+        if let IpiDestination::Logical(_) = self.destination {
+            flags |= InterruptCommandFlags::DESTINATION_MODE;
+        }
+        if self.level == IcrLevel::Assert {
+            flags |= InterruptCommandFlags::LEVEL;
+        }
+        if self.trigger_mode == IcrTriggerMode::Level {
+            flags |= InterruptCommandFlags::TRIGGER_MODE;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl TryFrom<Ipi> for InterruptCommandFlags {
+    type Error = &'static str;
+
+    fn try_from(ipi: Ipi) -> Result<Self, Self::Error> {
+        ipi.to_flags()
+    }
+}
+
 pub struct InterruptCommandRegister;
 impl LocalApicRegister for InterruptCommandRegister {
     type Value = InterruptCommandFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        let low = apic.read_reg_32(LocalApicRegisterIndex::InterruptCommand0);
-        let high = apic.read_reg_32(LocalApicRegisterIndex::InterruptCommand1);
+impl ReadableLocalApicRegister for InterruptCommandRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let low = apic.try_read_reg_32(LocalApicRegisterIndex::InterruptCommand0)?;
+        let high = apic.try_read_reg_32(LocalApicRegisterIndex::InterruptCommand1)?;
 
-        Self::Value::from_bits(((high as u64) << 32) | low as u64).unwrap()
+        let value = ((high as u64) << 32) | low as u64;
+        Self::Value::from_bits(value).ok_or(RegisterError::UndefinedBits(value))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
+impl WritableLocalApicRegister for InterruptCommandRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
         let low = value.low_word();
         let high = value.high_word();
 
-        apic.write_reg_32(LocalApicRegisterIndex::InterruptCommand0, low);
-        apic.write_reg_32(LocalApicRegisterIndex::InterruptCommand1, high);
+        // writing the low dword sends the ipi, so the destination goes first
+        apic.try_write_reg_32(LocalApicRegisterIndex::InterruptCommand1, high)?;
+        apic.try_write_reg_32(LocalApicRegisterIndex::InterruptCommand0, low)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_fixed_ipi() {
+        let flags = Ipi::fixed(InterruptVector(0x40), IpiDestination::Physical(3)).to_flags().expect("flags");
+
+        assert_eq!(flags.bits(), 0x0300_0000_0000_4040);
+        assert_eq!(flags.vector(), InterruptVector(0x40));
+        assert_eq!(flags.destination(), 3);
+        assert_eq!(flags.delivery_mode(), Ok(IcrDeliveryMode::Fixed));
+        assert_eq!(flags.destination_mode(), IcrDestinationMode::Physical);
+        assert_eq!(flags.destination_shorthand(), Ok(IcrDestinationShorthand::NoShorthand));
+        assert_eq!(flags.level(), IcrLevel::Assert);
+        assert_eq!(flags.trigger_mode(), IcrTriggerMode::Edge);
+    }
+
+    #[test]
+    pub fn test_init_sipi() {
+        let flags = Ipi::init_assert(IpiDestination::Physical(1)).to_flags().expect("flags");
+        assert_eq!(flags.bits(), 0x0100_0000_0000_c500);
+
+        let flags = Ipi::init_deassert(IpiDestination::Physical(1)).to_flags().expect("flags");
+        assert_eq!(flags.bits(), 0x0100_0000_0000_8500);
+        assert_eq!(flags.level(), IcrLevel::Deassert);
+
+        let flags = Ipi::startup(0x08, IpiDestination::Physical(1)).to_flags().expect("flags");
+        assert_eq!(flags.bits(), 0x0100_0000_0000_4608);
+    }
+
+    #[test]
+    pub fn test_shorthand_and_logical() {
+        let flags = Ipi::nmi(IpiDestination::Shorthand(IcrDestinationShorthand::AllExcludingSelf)).to_flags().expect("flags");
+        assert_eq!(flags.destination_shorthand(), Ok(IcrDestinationShorthand::AllExcludingSelf));
+        assert_eq!(flags.delivery_mode(), Ok(IcrDeliveryMode::NMI));
+
+        let flags = Ipi::fixed(InterruptVector(0x50), IpiDestination::Logical(0x0f)).to_flags().expect("flags");
+        assert_eq!(flags.destination_mode(), IcrDestinationMode::Logical);
+        assert_eq!(flags.destination(), 0x0f);
+    }
+
+    #[test]
+    pub fn test_invalid_ipis() {
+        let to_self = IpiDestination::Shorthand(IcrDestinationShorthand::ToSelf);
+        let all = IpiDestination::Shorthand(IcrDestinationShorthand::AllIncludingSelf);
+
+        assert!(Ipi::lowest_priority(InterruptVector(0x40), to_self).to_flags().is_err());
+        assert!(Ipi::lowest_priority(InterruptVector(0x40), all).to_flags().is_err());
+        assert!(Ipi::nmi(to_self).to_flags().is_err());
+        assert!(Ipi::startup(0x08, all).to_flags().is_err());
+        assert!(Ipi::fixed(InterruptVector(0x02), IpiDestination::Physical(0)).to_flags().is_err());
+        assert!(Ipi::fixed(InterruptVector(0x40), IpiDestination::Physical(0x100)).to_flags().is_err());
+    }
+
+    #[test]
+    pub fn test_x2apic_destination() {
+        let flags = Ipi::fixed(InterruptVector(0x40), IpiDestination::Physical(0x1234)).to_x2apic_flags().expect("flags");
+
+        assert_eq!(flags.bits(), 0x0000_1234_0000_4040);
+        assert_eq!(flags.x2apic_destination(), 0x1234);
+    }
+}
@@ -1,12 +1,13 @@
 use super::LocalApic;
-use super::registers::{LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use super::registers::{LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct IdFlags: u32 {
         const ALL       = 0xffff_ffff;
         const ID_4_BIT  = 0x0f00_0000;
         const ID_8_BIT  = 0xff00_0000;
-        // read 32 bit addr for x2 mode from MSR 802h
+        // read 32 bit x2apic id with X2ApicIdRegister
     }
 }
 
@@ -31,7 +32,7 @@ impl IdFlags {
 
 impl From<u32> for IdFlags {
     fn from(value: u32) -> Self {
-        IdFlags::from_bits(value).unwrap()
+        IdFlags::from_bits_truncate(value)
     }
 }
 
@@ -50,25 +51,33 @@ pub enum ApicId {
 pub struct Id4BitRegister;
 impl LocalApicRegister for Id4BitRegister {
     type Value = ApicId;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        IdFlags::from(apic.read_reg_32(LocalApicRegisterIndex::Id)).id_4_bit()
+impl ReadableLocalApicRegister for Id4BitRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(IdFlags::from(apic.try_read_reg_32(LocalApicRegisterIndex::Id)?).id_4_bit())
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::Id, IdFlags::from(value).bits())
+impl WritableLocalApicRegister for Id4BitRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::Id, IdFlags::from(value).bits())
     }
 }
 
 pub struct Id8BitRegister;
 impl LocalApicRegister for Id8BitRegister {
     type Value = ApicId;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        IdFlags::from(apic.read_reg_32(LocalApicRegisterIndex::Id)).id_8_bit()
+impl ReadableLocalApicRegister for Id8BitRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(IdFlags::from(apic.try_read_reg_32(LocalApicRegisterIndex::Id)?).id_8_bit())
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::Id, IdFlags::from(value).bits());
+impl WritableLocalApicRegister for Id8BitRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::Id, IdFlags::from(value).bits())
     }
 }
\ No newline at end of file
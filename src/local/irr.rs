@@ -1,3 +1,18 @@
-pub struct InterruptRequestRegister {
-    // 8 x 32 bits starting at fee00200h ending at fee00270h
-}
\ No newline at end of file
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex, InterruptVectorSet};
+
+pub struct InterruptRequestRegister;
+impl LocalApicRegister for InterruptRequestRegister {
+    type Value = InterruptVectorSet;
+}
+
+impl ReadableLocalApicRegister for InterruptRequestRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let mut words = [0; 8];
+        for (word, index) in words.iter_mut().zip(LocalApicRegisterIndex::INTERRUPT_REQUEST.iter()) {
+            *word = apic.try_read_reg_32(*index)?;
+        }
+
+        Ok(InterruptVectorSet::from_words(words))
+    }
+}
@@ -1,3 +1,18 @@
-pub struct InServiceRegister {
-    // 8 x 32 bits starting at fee00100h ending at fee00170h
-}
\ No newline at end of file
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex, InterruptVectorSet};
+
+pub struct InServiceRegister;
+impl LocalApicRegister for InServiceRegister {
+    type Value = InterruptVectorSet;
+}
+
+impl ReadableLocalApicRegister for InServiceRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let mut words = [0; 8];
+        for (word, index) in words.iter_mut().zip(LocalApicRegisterIndex::IN_SERVICE.iter()) {
+            *word = apic.try_read_reg_32(*index)?;
+        }
+
+        Ok(InterruptVectorSet::from_words(words))
+    }
+}
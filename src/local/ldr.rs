@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct LogicalDestinationFlags: u32 {
@@ -31,12 +32,16 @@ impl From<LogicalApicId> for LogicalDestinationFlags {
 pub struct LogicalDestinationRegister;
 impl LocalApicRegister for LogicalDestinationRegister {
     type Value = LogicalDestinationFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        LogicalDestinationFlags::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LogicalDestination))
+impl ReadableLocalApicRegister for LogicalDestinationRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(LogicalDestinationFlags::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LogicalDestination)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LogicalDestination, LogicalDestinationFlags::from(value).bits());
+impl WritableLocalApicRegister for LogicalDestinationRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LogicalDestination, value.bits())
     }
 }
\ No newline at end of file
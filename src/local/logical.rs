@@ -0,0 +1,195 @@
+use core::result::Result;
+use crate::io::Destination;
+use crate::local::{
+    DestinationFormatFlags, DestinationFormatModel, IpiDestination, LogicalApicId, LogicalDestinationFlags,
+};
+
+pub const FLAT_MAX_CPUS: u8 = 8;
+pub const CLUSTER_MAX_MEMBERS: u8 = 4;
+pub const CLUSTER_BROADCAST: u8 = 0xf;
+pub const LOGICAL_BROADCAST: u8 = 0xff;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LogicalDestinationError {
+    CpuOutOfRange(u8),
+    ClusterOutOfRange(u8),
+    MemberOutOfRange(u8),
+    ReservedModel(u32),
+    Unaddressable(LogicalApicId),
+}
+
+impl LogicalApicId {
+    pub fn flat(cpu: u8) -> Result<Self, LogicalDestinationError> {
+        if cpu >= FLAT_MAX_CPUS {
+            return Err(LogicalDestinationError::CpuOutOfRange(cpu));
+        }
+
+        Ok(LogicalApicId(1 << cpu))
+    }
+
+    pub fn cluster(cluster: u8, member: u8) -> Result<Self, LogicalDestinationError> {
+        if cluster >= CLUSTER_BROADCAST {
+            return Err(LogicalDestinationError::ClusterOutOfRange(cluster));
+        }
+        if member >= CLUSTER_MAX_MEMBERS {
+            return Err(LogicalDestinationError::MemberOutOfRange(member));
+        }
+
+        Ok(LogicalApicId(((cluster as u32) << 4) | (1 << member)))
+    }
+
+    pub fn cluster_id(&self) -> u8 {
+        ((self.0 >> 4) & 0xf) as u8
+    }
+
+    pub fn members(&self) -> u8 {
+        (self.0 & 0xf) as u8
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LogicalDestination {
+    pub model: DestinationFormatModel,
+    pub mask: u8,
+}
+
+impl LogicalDestination {
+    pub fn new(model: DestinationFormatModel, mask: u8) -> Self {
+        LogicalDestination { model, mask }
+    }
+
+    pub fn broadcast(model: DestinationFormatModel) -> Self {
+        LogicalDestination::new(model, LOGICAL_BROADCAST)
+    }
+
+    pub fn flat(cpus: &[u8]) -> Result<Self, LogicalDestinationError> {
+        let mut mask = 0;
+        for cpu in cpus {
+            mask |= LogicalApicId::flat(*cpu)?.0 as u8;
+        }
+
+        Ok(LogicalDestination::new(DestinationFormatModel::Flat, mask))
+    }
+
+    pub fn cluster(cluster: u8, members: &[u8]) -> Result<Self, LogicalDestinationError> {
+        if cluster > CLUSTER_BROADCAST {
+            return Err(LogicalDestinationError::ClusterOutOfRange(cluster));
+        }
+
+        let mut mask = cluster << 4;
+        for member in members {
+            if *member >= CLUSTER_MAX_MEMBERS {
+                return Err(LogicalDestinationError::MemberOutOfRange(*member));
+            }
+            mask |= 1 << member;
+        }
+
+        Ok(LogicalDestination::new(DestinationFormatModel::Cluster, mask))
+    }
+
+    pub fn matches(&self, id: LogicalApicId) -> bool {
+        let id = id.0 as u8;
+
+        match self.model {
+            DestinationFormatModel::Flat => self.mask & id != 0,
+            DestinationFormatModel::Cluster => {
+                let cluster = self.mask >> 4;
+                (cluster == CLUSTER_BROADCAST || cluster == id >> 4) && self.mask & id & 0xf != 0
+            }
+        }
+    }
+
+    pub fn matching<'a>(&'a self, cpus: &'a [LogicalApicId]) -> impl Iterator<Item = usize> + 'a {
+        cpus.iter().enumerate()
+            .filter(move |(_, id)| self.matches(**id))
+            .map(|(cpu, _)| cpu)
+    }
+}
+
+impl From<LogicalDestination> for Destination {
+    fn from(destination: LogicalDestination) -> Self {
+        Destination::Logical(destination.mask)
+    }
+}
+
+impl From<LogicalDestination> for IpiDestination {
+    fn from(destination: LogicalDestination) -> Self {
+        IpiDestination::Logical(destination.mask as u32)
+    }
+}
+
+pub fn validate_logical_destination(format: DestinationFormatFlags, logical: LogicalDestinationFlags)
+    -> Result<DestinationFormatModel, LogicalDestinationError> {
+    let model = (format & DestinationFormatFlags::MODEL).bits() >> 28;
+    if model != DestinationFormatModel::Flat.as_u32() && model != DestinationFormatModel::Cluster.as_u32() {
+        return Err(LogicalDestinationError::ReservedModel(model));
+    }
+
+    let id = logical.logical_apic_id();
+    match format.model() {
+        DestinationFormatModel::Flat if id.0 == 0 => Err(LogicalDestinationError::Unaddressable(id)),
+        DestinationFormatModel::Cluster if id.cluster_id() == CLUSTER_BROADCAST => {
+            Err(LogicalDestinationError::ClusterOutOfRange(id.cluster_id()))
+        }
+        DestinationFormatModel::Cluster if id.members() == 0 => Err(LogicalDestinationError::Unaddressable(id)),
+        model => Ok(model),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_flat() {
+        let cpus = [LogicalApicId::flat(0).unwrap(), LogicalApicId::flat(3).unwrap(), LogicalApicId::flat(7).unwrap()];
+        assert_eq!(cpus[2], LogicalApicId(0x80));
+        assert_eq!(LogicalApicId::flat(8), Err(LogicalDestinationError::CpuOutOfRange(8)));
+
+        let destination = LogicalDestination::flat(&[0, 7]).unwrap();
+        assert_eq!(destination.mask, 0x81);
+        assert!(destination.matching(&cpus).eq([0, 2].iter().copied()));
+        assert_eq!(LogicalDestination::broadcast(DestinationFormatModel::Flat).matching(&cpus).count(), 3);
+        assert_eq!(Destination::from(destination), Destination::Logical(0x81));
+    }
+
+    #[test]
+    pub fn test_cluster() {
+        let cpus = [
+            LogicalApicId::cluster(0, 0).unwrap(),
+            LogicalApicId::cluster(0, 1).unwrap(),
+            LogicalApicId::cluster(2, 1).unwrap(),
+            LogicalApicId::cluster(2, 3).unwrap(),
+        ];
+        assert_eq!(cpus[3], LogicalApicId(0x28));
+        assert_eq!(cpus[3].cluster_id(), 2);
+        assert_eq!(LogicalApicId::cluster(15, 0), Err(LogicalDestinationError::ClusterOutOfRange(15)));
+        assert_eq!(LogicalApicId::cluster(0, 4), Err(LogicalDestinationError::MemberOutOfRange(4)));
+
+        let destination = LogicalDestination::cluster(2, &[1, 3]).unwrap();
+        assert_eq!(destination.mask, 0x2a);
+        assert!(destination.matching(&cpus).eq([2, 3].iter().copied()));
+
+        // the broadcast cluster selects the member bits in every cluster
+        let destination = LogicalDestination::cluster(CLUSTER_BROADCAST, &[1]).unwrap();
+        assert!(destination.matching(&cpus).eq([1, 2].iter().copied()));
+        assert_eq!(IpiDestination::from(destination), IpiDestination::Logical(0xf2));
+    }
+
+    #[test]
+    pub fn test_validate() {
+        let flat = DestinationFormatFlags::flat();
+        let cluster = DestinationFormatFlags::cluster();
+
+        assert_eq!(validate_logical_destination(flat, LogicalApicId(0x04).into()), Ok(DestinationFormatModel::Flat));
+        assert_eq!(validate_logical_destination(flat, LogicalApicId(0).into()),
+            Err(LogicalDestinationError::Unaddressable(LogicalApicId(0))));
+        assert_eq!(validate_logical_destination(cluster, LogicalApicId(0x21).into()), Ok(DestinationFormatModel::Cluster));
+        assert_eq!(validate_logical_destination(cluster, LogicalApicId(0xf1).into()),
+            Err(LogicalDestinationError::ClusterOutOfRange(0xf)));
+        assert_eq!(validate_logical_destination(cluster, LogicalApicId(0x20).into()),
+            Err(LogicalDestinationError::Unaddressable(LogicalApicId(0x20))));
+        assert_eq!(validate_logical_destination(DestinationFormatFlags::from_bits_truncate(0x5fff_ffff), LogicalApicId(0x01).into()),
+            Err(LogicalDestinationError::ReservedModel(0x5)));
+    }
+}
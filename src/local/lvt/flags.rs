@@ -1,4 +1,6 @@
 use core::convert::TryFrom;
+use core::result::Result;
+use crate::error::RegisterError;
 use crate::local::InterruptVector;
 
 bitflags! {
@@ -25,9 +27,9 @@ impl LvtFlags {
         InterruptVector((*self & LvtFlags::VECTOR).bits())
     }
 
-    pub fn delivery_mode(&self) -> LvtDeliveryMode {
-        LvtDeliveryMode::try_from((*self & LvtFlags::DELIVERY_MODE).bits() >> 8)
-            .expect("delivery mode")
+    pub fn delivery_mode(&self) -> Result<LvtDeliveryMode, RegisterError> {
+        let value = (*self & LvtFlags::DELIVERY_MODE).bits() >> 8;
+        LvtDeliveryMode::try_from(value).map_err(|_| RegisterError::ReservedValue { field: "delivery mode", value })
     }
 
     pub fn delivery_status(&self) -> LvtDeliveryStatus {
@@ -50,14 +52,14 @@ impl LvtFlags {
         self.contains(LvtFlags::MASK).into()
     }
 
-    pub fn timer_mode_1_bit(&self) -> LvtTimerMode {
-        LvtTimerMode::try_from((*self & LvtFlags::TIMER_MODE_1_BIT).bits() >> 16)
-            .expect("timer mode")
+    pub fn timer_mode_1_bit(&self) -> Result<LvtTimerMode, RegisterError> {
+        let value = (*self & LvtFlags::TIMER_MODE_1_BIT).bits() >> 17;
+        LvtTimerMode::try_from(value).map_err(|_| RegisterError::ReservedValue { field: "timer mode", value })
     }
 
-    pub fn timer_mode_2_bit(&self) -> LvtTimerMode {
-        LvtTimerMode::try_from((*self & LvtFlags::TIMER_MODE_2_BIT).bits() >> 16)
-            .expect("timer mode")
+    pub fn timer_mode_2_bit(&self) -> Result<LvtTimerMode, RegisterError> {
+        let value = (*self & LvtFlags::TIMER_MODE_2_BIT).bits() >> 17;
+        LvtTimerMode::try_from(value).map_err(|_| RegisterError::ReservedValue { field: "timer mode", value })
     }
 }
 
@@ -93,7 +95,7 @@ impl From<LvtMask> for LvtFlags {
 
 impl From<LvtTimerMode> for LvtFlags {
     fn from(mode: LvtTimerMode) -> LvtFlags {
-        Self::from_bits_truncate(mode.as_u32() << 16)
+        Self::from_bits_truncate(mode.as_u32() << 17)
     }
 }
 
@@ -284,3 +286,22 @@ impl TryFrom<u8> for LvtDeliveryMode {
         Self::try_from(value as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_timer_mode_conversions() {
+        let flags = LvtFlags::from(LvtTimerMode::Periodic);
+        assert_eq!(flags.mask(), LvtMask::NotMasked);
+        assert_eq!(flags.timer_mode_2_bit(), Ok(LvtTimerMode::Periodic));
+
+        let flags = LvtFlags::from(LvtTimerMode::TSCDeadline) | LvtFlags::from(LvtMask::Masked);
+        assert_eq!(flags.mask(), LvtMask::Masked);
+        assert_eq!(flags.timer_mode_2_bit(), Ok(LvtTimerMode::TSCDeadline));
+        assert_eq!(LvtFlags::TIMER_MODE_2_BIT.timer_mode_2_bit(),
+            Err(RegisterError::ReservedValue { field: "timer mode", value: 0x3 }));
+        assert_eq!(LvtFlags::TIMER_MODE_2_BIT.timer_mode_1_bit(), Ok(LvtTimerMode::Periodic));
+    }
+}
@@ -1,94 +1,125 @@
 
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
-use super::{LvtFlags};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
+use core::result::Result;
+use super::LvtFlags;
 
 pub struct LvtTimerRegister;
 impl LocalApicRegister for LvtTimerRegister {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtTimer))
+impl ReadableLocalApicRegister for LvtTimerRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let value = Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtTimer)?);
+        value.timer_mode_2_bit().map(|_| value)
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtTimer, value.bits());
+impl WritableLocalApicRegister for LvtTimerRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtTimer, value.bits())
     }
 }
 
 pub struct LvtCmciRegister;
 impl LocalApicRegister for LvtCmciRegister {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtCmci))
+impl ReadableLocalApicRegister for LvtCmciRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtCmci)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtCmci, value.bits());
+impl WritableLocalApicRegister for LvtCmciRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtCmci, value.bits())
     }
 }
 
 pub struct LvtLint0Register;
 impl LocalApicRegister for LvtLint0Register {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtLINT0))
+impl ReadableLocalApicRegister for LvtLint0Register {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtLINT0)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtLINT0, value.bits());
+impl WritableLocalApicRegister for LvtLint0Register {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtLINT0, value.bits())
     }
 }
 
 pub struct LvtLint1Register;
 impl LocalApicRegister for LvtLint1Register {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtLINT1))
+impl ReadableLocalApicRegister for LvtLint1Register {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtLINT1)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtLINT1, value.bits());
+impl WritableLocalApicRegister for LvtLint1Register {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtLINT1, value.bits())
     }
 }
 
 pub struct LvtErrorRegister;
 impl LocalApicRegister for LvtErrorRegister {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtError))
+impl ReadableLocalApicRegister for LvtErrorRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtError)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtError, value.bits());
+impl WritableLocalApicRegister for LvtErrorRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtError, value.bits())
     }
 }
 
 pub struct LvtPerfCountersRegister;
 impl LocalApicRegister for LvtPerfCountersRegister {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtPerfCounters))
+impl ReadableLocalApicRegister for LvtPerfCountersRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtPerfCounters)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtPerfCounters, value.bits());
+impl WritableLocalApicRegister for LvtPerfCountersRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtPerfCounters, value.bits())
     }
 }
 
 pub struct LvtThermalSensorRegister;
 impl LocalApicRegister for LvtThermalSensorRegister {
     type Value = LvtFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::LvtThermalSensor))
+impl ReadableLocalApicRegister for LvtThermalSensorRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::LvtThermalSensor)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::LvtThermalSensor, value.bits());
+impl WritableLocalApicRegister for LvtThermalSensorRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::LvtThermalSensor, value.bits())
     }
 }
@@ -1,39 +1,65 @@
+pub mod allocator;
+pub mod apic_timer;
 pub mod apr;
+pub mod base;
+pub mod calibration;
 pub mod dfr;
 pub mod eoi;
+#[cfg(any(test, feature = "emulation"))]
+pub mod emulated;
 pub mod esr;
 pub mod icr;
 pub mod id;
 pub mod irr;
 pub mod isr;
 pub mod ldr;
+pub mod logical;
 pub mod lvt;
 pub mod ppr;
+pub mod priority;
+pub mod priority_guard;
 pub mod sivr;
+pub mod startup;
 pub mod timer;
 pub mod tmr;
 pub mod tpr;
+pub mod vector_set;
 pub mod version;
+pub mod x2apic;
 pub mod registers;
 
+pub use allocator::*;
+pub use apic_timer::*;
 pub use apr::*;
+pub use base::*;
+pub use calibration::*;
 pub use dfr::*;
 pub use eoi::*;
+#[cfg(any(test, feature = "emulation"))]
+pub use emulated::*;
 pub use esr::*;
 pub use icr::*;
 pub use id::*;
 pub use irr::*;
 pub use isr::*;
 pub use ldr::*;
+pub use logical::*;
 pub use lvt::*;
 pub use ppr::*;
+pub use priority::*;
+pub use priority_guard::*;
 pub use sivr::*;
+pub use startup::*;
 pub use timer::*;
 pub use tmr::*;
 pub use tpr::*;
+pub use vector_set::*;
 pub use version::*;
+pub use x2apic::*;
 pub use registers::*;
 
+use core::result::Result;
+use crate::error::RegisterError;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PriorityClass(pub u32);
@@ -54,7 +80,41 @@ impl InterruptVector {
     }
 }
 
+/// # Safety
+///
+/// Implementations must refer to a live, correctly mapped local apic. Every
+/// unsafe register accessor in this crate relies on that and nothing else.
 pub trait LocalApic {
     unsafe fn read_reg_32(&self, index: LocalApicRegisterIndex) -> u32;
     unsafe fn write_reg_32(&self, index: LocalApicRegisterIndex, value: u32);
+
+    /// The mode this backend drives the apic in, which decides the icr layout.
+    fn mode(&self) -> ApicMode {
+        ApicMode::XApic
+    }
+
+    /// Whether the register exists on this backend. Typed register accessors
+    /// return `RegisterError::Unsupported` for registers that do not.
+    fn supports(&self, _index: LocalApicRegisterIndex) -> bool {
+        true
+    }
+}
+
+impl dyn LocalApic + '_ {
+    pub unsafe fn try_read_reg_32(&self, index: LocalApicRegisterIndex) -> Result<u32, RegisterError> {
+        if !self.supports(index) {
+            return Err(RegisterError::Unsupported(index));
+        }
+
+        Ok(self.read_reg_32(index))
+    }
+
+    pub unsafe fn try_write_reg_32(&self, index: LocalApicRegisterIndex, value: u32) -> Result<(), RegisterError> {
+        if !self.supports(index) {
+            return Err(RegisterError::Unsupported(index));
+        }
+
+        self.write_reg_32(index, value);
+        Ok(())
+    }
 }
@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
 
 bitflags! {
     pub struct ProcessorPriorityFlags: u32 {
@@ -33,13 +34,12 @@ impl From<PrioritySubClass> for ProcessorPriorityFlags {
 pub struct ProcessorPriorityRegister;
 impl LocalApicRegister for ProcessorPriorityRegister {
     type Value = ProcessorPriorityFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        ProcessorPriorityFlags::from_bits(apic.read_reg_32(LocalApicRegisterIndex::ProcessorPriority)).unwrap()
-    }
-
-    unsafe fn write(&self, _apic: &dyn LocalApic, _value: Self::Value) {
-        panic!("processor priority is read-only");
+impl ReadableLocalApicRegister for ProcessorPriorityRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let value = apic.try_read_reg_32(LocalApicRegisterIndex::ProcessorPriority)?;
+        ProcessorPriorityFlags::from_bits(value).ok_or(RegisterError::UndefinedBits(value as u64))
     }
 }
 
@@ -0,0 +1,134 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, InterruptVector, InterruptVectorSet, PriorityClass, TaskPriorityFlags,
+    ProcessorPriorityFlags, ArbitrationPriorityFlags, TaskPriorityRegister, InServiceRegister,
+    InterruptRequestRegister,
+};
+
+pub fn processor_priority(task_priority: TaskPriorityFlags, highest_in_service: Option<InterruptVector>) -> ProcessorPriorityFlags {
+    let in_service_class = highest_in_service.map(|vector| vector.priority_class()).unwrap_or(PriorityClass(0));
+
+    if task_priority.priority_class() >= in_service_class {
+        ProcessorPriorityFlags::from_bits_truncate(task_priority.bits()) & !ProcessorPriorityFlags::RESERVED
+    } else {
+        ProcessorPriorityFlags::from(in_service_class)
+    }
+}
+
+pub fn arbitration_priority(task_priority: TaskPriorityFlags, highest_in_service: Option<InterruptVector>,
+    highest_request: Option<InterruptVector>) -> ArbitrationPriorityFlags {
+    let task_class = task_priority.priority_class();
+    let in_service_class = highest_in_service.map(|vector| vector.priority_class()).unwrap_or(PriorityClass(0));
+    let request_class = highest_request.map(|vector| vector.priority_class()).unwrap_or(PriorityClass(0));
+
+    if task_class >= request_class && task_class > in_service_class {
+        ArbitrationPriorityFlags::from_bits_truncate(task_priority.bits()) & !ArbitrationPriorityFlags::RESERVED
+    } else {
+        ArbitrationPriorityFlags::from(task_class.max(in_service_class).max(request_class))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InterruptBlocked {
+    NotRequested,
+    BelowProcessorPriority(ProcessorPriorityFlags),
+    HigherRequestPending(InterruptVector),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PrioritySnapshot {
+    pub task_priority: TaskPriorityFlags,
+    pub in_service: InterruptVectorSet,
+    pub interrupt_request: InterruptVectorSet,
+}
+
+impl PrioritySnapshot {
+    pub unsafe fn read(apic: &dyn LocalApic) -> Result<Self, RegisterError> {
+        Ok(PrioritySnapshot {
+            task_priority: TaskPriorityRegister.read(apic)?,
+            in_service: InServiceRegister.read(apic)?,
+            interrupt_request: InterruptRequestRegister.read(apic)?,
+        })
+    }
+
+    pub fn processor_priority(&self) -> ProcessorPriorityFlags {
+        processor_priority(self.task_priority, self.in_service.highest())
+    }
+
+    pub fn arbitration_priority(&self) -> ArbitrationPriorityFlags {
+        arbitration_priority(self.task_priority, self.in_service.highest(), self.interrupt_request.highest())
+    }
+
+    pub fn next_delivery(&self) -> Option<InterruptVector> {
+        self.interrupt_request.highest()
+            .filter(|vector| vector.priority_class() > self.processor_priority().priority_class())
+    }
+
+    pub fn would_deliver(&self, vector: InterruptVector) -> Result<(), InterruptBlocked> {
+        if !self.interrupt_request.contains(vector) {
+            return Err(InterruptBlocked::NotRequested);
+        }
+
+        let processor_priority = self.processor_priority();
+        if vector.priority_class() <= processor_priority.priority_class() {
+            return Err(InterruptBlocked::BelowProcessorPriority(processor_priority));
+        }
+
+        match self.interrupt_request.highest() {
+            Some(highest) if highest != vector => Err(InterruptBlocked::HigherRequestPending(highest)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(vectors: &[u32]) -> InterruptVectorSet {
+        let mut set = InterruptVectorSet::new();
+        for vector in vectors {
+            set.insert(InterruptVector(*vector));
+        }
+        set
+    }
+
+    #[test]
+    pub fn test_processor_priority() {
+        let tpr = TaskPriorityFlags::from_bits_truncate(0x45);
+
+        assert_eq!(processor_priority(tpr, None).bits(), 0x45);
+        assert_eq!(processor_priority(tpr, Some(InterruptVector(0x4f))).bits(), 0x45);
+        assert_eq!(processor_priority(tpr, Some(InterruptVector(0x51))).bits(), 0x50);
+    }
+
+    #[test]
+    pub fn test_arbitration_priority() {
+        let tpr = TaskPriorityFlags::from_bits_truncate(0x45);
+
+        assert_eq!(arbitration_priority(tpr, None, None).bits(), 0x45);
+        assert_eq!(arbitration_priority(tpr, None, Some(InterruptVector(0x61))).bits(), 0x60);
+        assert_eq!(arbitration_priority(tpr, Some(InterruptVector(0x41)), None).bits(), 0x40);
+        assert_eq!(arbitration_priority(tpr, Some(InterruptVector(0x71)), Some(InterruptVector(0x61))).bits(), 0x70);
+    }
+
+    #[test]
+    pub fn test_would_deliver() {
+        let snapshot = PrioritySnapshot {
+            task_priority: TaskPriorityFlags::from_bits_truncate(0x30),
+            in_service: set(&[0x51]),
+            interrupt_request: set(&[0x41, 0x52, 0x61]),
+        };
+
+        assert_eq!(snapshot.processor_priority().bits(), 0x50);
+        assert_eq!(snapshot.next_delivery(), Some(InterruptVector(0x61)));
+        assert_eq!(snapshot.would_deliver(InterruptVector(0x61)), Ok(()));
+        assert_eq!(snapshot.would_deliver(InterruptVector(0x70)), Err(InterruptBlocked::NotRequested));
+        assert_eq!(snapshot.would_deliver(InterruptVector(0x52)),
+            Err(InterruptBlocked::BelowProcessorPriority(ProcessorPriorityFlags::from_bits_truncate(0x50))));
+
+        let snapshot = PrioritySnapshot { interrupt_request: set(&[0x61, 0x71]), ..snapshot };
+        assert_eq!(snapshot.would_deliver(InterruptVector(0x61)), Err(InterruptBlocked::HigherRequestPending(InterruptVector(0x71))));
+    }
+}
@@ -0,0 +1,204 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, WritableLocalApicRegister, PriorityClass, TaskPriorityFlags,
+    TaskPriorityRegister,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PriorityError {
+    InvalidClass(PriorityClass),
+    WouldLower { current: PriorityClass, requested: PriorityClass },
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for PriorityError {
+    fn from(error: RegisterError) -> Self {
+        PriorityError::Register(error)
+    }
+}
+
+fn check_raise(current: PriorityClass, requested: PriorityClass) -> Result<(), PriorityError> {
+    if requested.0 > 0xf {
+        Err(PriorityError::InvalidClass(requested))
+    } else if requested < current {
+        Err(PriorityError::WouldLower { current, requested })
+    } else {
+        Ok(())
+    }
+}
+
+/// Raises the task priority and puts it back when dropped. Guards only restore
+/// correctly when dropped innermost first, so take further raises from the
+/// outer guard with `raise_nested`, which keeps it borrowed:
+///
+/// ```compile_fail
+/// use apic_types::local::{LocalApic, PriorityClass, TaskPriorityGuard};
+///
+/// unsafe fn unbalanced(apic: &dyn LocalApic) {
+///     let mut outer = TaskPriorityGuard::raise(apic, PriorityClass(4)).unwrap();
+///     let inner = outer.raise_nested(PriorityClass(9)).unwrap();
+///     drop(outer);
+///     drop(inner);
+/// }
+/// ```
+pub struct TaskPriorityGuard<'a> {
+    apic: &'a dyn LocalApic,
+    previous: TaskPriorityFlags,
+}
+
+impl<'a> TaskPriorityGuard<'a> {
+    pub unsafe fn raise(apic: &'a dyn LocalApic, class: PriorityClass) -> Result<Self, PriorityError> {
+        let previous = TaskPriorityRegister.read(apic)?;
+        check_raise(previous.priority_class(), class)?;
+
+        if class > previous.priority_class() {
+            TaskPriorityRegister.write(apic, TaskPriorityFlags::from(class))?;
+        }
+
+        Ok(TaskPriorityGuard { apic, previous })
+    }
+
+    pub unsafe fn raise_nested(&mut self, class: PriorityClass) -> Result<TaskPriorityGuard<'_>, PriorityError> {
+        TaskPriorityGuard::raise(self.apic, class)
+    }
+
+    pub fn previous(&self) -> TaskPriorityFlags {
+        self.previous
+    }
+}
+
+impl<'a> Drop for TaskPriorityGuard<'a> {
+    fn drop(&mut self) {
+        // the tpr was readable when the guard was raised, so restoring it should not fail
+        let restored = unsafe { TaskPriorityRegister.write(self.apic, self.previous) };
+        debug_assert!(restored.is_ok(), "failed to restore the task priority");
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub trait Cr8 {
+    unsafe fn read_cr8(&self) -> u64;
+    unsafe fn write_cr8(&self, value: u64);
+}
+
+/// Raises cr8 and puts it back when dropped. As with `TaskPriorityGuard`, take
+/// further raises from the outer guard with `raise_nested`.
+#[cfg(target_arch = "x86_64")]
+pub struct Cr8PriorityGuard<'a> {
+    cr8: &'a dyn Cr8,
+    previous: PriorityClass,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl<'a> Cr8PriorityGuard<'a> {
+    pub unsafe fn raise(cr8: &'a dyn Cr8, class: PriorityClass) -> Result<Self, PriorityError> {
+        let previous = PriorityClass((cr8.read_cr8() & 0xf) as u32);
+        check_raise(previous, class)?;
+
+        if class > previous {
+            cr8.write_cr8(class.0 as u64);
+        }
+
+        Ok(Cr8PriorityGuard { cr8, previous })
+    }
+
+    pub unsafe fn raise_nested(&mut self, class: PriorityClass) -> Result<Cr8PriorityGuard<'_>, PriorityError> {
+        Cr8PriorityGuard::raise(self.cr8, class)
+    }
+
+    pub fn previous(&self) -> PriorityClass {
+        self.previous
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl<'a> Drop for Cr8PriorityGuard<'a> {
+    fn drop(&mut self) {
+        unsafe { self.cr8.write_cr8(self.previous.0 as u64) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::cell::Cell;
+    use crate::local::EmulatedLocalApic;
+
+    fn task_priority(apic: &EmulatedLocalApic) -> u32 {
+        unsafe { TaskPriorityRegister.read(apic).unwrap().bits() }
+    }
+
+    #[test]
+    pub fn test_nested_guards() {
+        let apic = EmulatedLocalApic::new(0);
+
+        unsafe {
+            TaskPriorityRegister.write(&apic, TaskPriorityFlags::from_bits_truncate(0x23)).unwrap();
+
+            let mut outer = TaskPriorityGuard::raise(&apic, PriorityClass(4)).unwrap();
+            assert_eq!(task_priority(&apic), 0x40);
+            {
+                let mut inner = outer.raise_nested(PriorityClass(9)).unwrap();
+                assert_eq!(task_priority(&apic), 0x90);
+
+                let same = inner.raise_nested(PriorityClass(9)).unwrap();
+                assert_eq!(same.previous().bits(), 0x90);
+            }
+            assert_eq!(task_priority(&apic), 0x40);
+            assert_eq!(outer.previous().bits(), 0x23);
+        }
+
+        assert_eq!(task_priority(&apic), 0x23);
+    }
+
+    #[test]
+    pub fn test_refuses_to_lower() {
+        let apic = EmulatedLocalApic::new(0);
+
+        unsafe {
+            let mut guard = TaskPriorityGuard::raise(&apic, PriorityClass(6)).unwrap();
+
+            assert_eq!(guard.raise_nested(PriorityClass(2)).err(),
+                Some(PriorityError::WouldLower { current: PriorityClass(6), requested: PriorityClass(2) }));
+            assert_eq!(guard.raise_nested(PriorityClass(16)).err(),
+                Some(PriorityError::InvalidClass(PriorityClass(16))));
+            assert_eq!(task_priority(&apic), 0x60);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    struct FakeCr8(Cell<u64>);
+
+    #[cfg(target_arch = "x86_64")]
+    impl Cr8 for FakeCr8 {
+        unsafe fn read_cr8(&self) -> u64 {
+            self.0.get()
+        }
+
+        unsafe fn write_cr8(&self, value: u64) {
+            self.0.set(value);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    pub fn test_cr8_guard() {
+        let cr8 = FakeCr8(Cell::new(2));
+
+        unsafe {
+            {
+                let mut outer = Cr8PriorityGuard::raise(&cr8, PriorityClass(5)).unwrap();
+                {
+                    let _inner = outer.raise_nested(PriorityClass(13)).unwrap();
+                    assert_eq!(cr8.0.get(), 13);
+                }
+                assert_eq!(cr8.0.get(), 5);
+                assert!(outer.raise_nested(PriorityClass(1)).is_err());
+            }
+        }
+
+        assert_eq!(cr8.0.get(), 2);
+    }
+}
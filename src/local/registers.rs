@@ -1,5 +1,8 @@
+use core::result::Result;
+use crate::error::RegisterError;
 use super::LocalApic;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u32)]
 pub enum LocalApicRegisterIndex {
     Id = 0x20,
@@ -49,14 +52,64 @@ pub enum LocalApicRegisterIndex {
     TimerInitialCount = 0x380,
     TimerCurrentCount = 0x390,
     TimerDivideConfiguration = 0x3e0,
+    SelfIpi = 0x3f0,
     // ...
 }
 
 impl LocalApicRegisterIndex {
+    pub const IN_SERVICE: [LocalApicRegisterIndex; 8] = [
+        LocalApicRegisterIndex::InService0,
+        LocalApicRegisterIndex::InService1,
+        LocalApicRegisterIndex::InService2,
+        LocalApicRegisterIndex::InService3,
+        LocalApicRegisterIndex::InService4,
+        LocalApicRegisterIndex::InService5,
+        LocalApicRegisterIndex::InService6,
+        LocalApicRegisterIndex::InService7,
+    ];
+
+    pub const TRIGGER_MODE: [LocalApicRegisterIndex; 8] = [
+        LocalApicRegisterIndex::TriggerMode0,
+        LocalApicRegisterIndex::TriggerMode1,
+        LocalApicRegisterIndex::TriggerMode2,
+        LocalApicRegisterIndex::TriggerMode3,
+        LocalApicRegisterIndex::TriggerMode4,
+        LocalApicRegisterIndex::TriggerMode5,
+        LocalApicRegisterIndex::TriggerMode6,
+        LocalApicRegisterIndex::TriggerMode7,
+    ];
+
+    pub const INTERRUPT_REQUEST: [LocalApicRegisterIndex; 8] = [
+        LocalApicRegisterIndex::InterruptRequest0,
+        LocalApicRegisterIndex::InterruptRequest1,
+        LocalApicRegisterIndex::InterruptRequest2,
+        LocalApicRegisterIndex::InterruptRequest3,
+        LocalApicRegisterIndex::InterruptRequest4,
+        LocalApicRegisterIndex::InterruptRequest5,
+        LocalApicRegisterIndex::InterruptRequest6,
+        LocalApicRegisterIndex::InterruptRequest7,
+    ];
+
     pub fn as_u32(self) -> u32 {
         self as u32
     }
 
+    pub fn is_readable(self) -> bool {
+        !matches!(self, LocalApicRegisterIndex::EndOfInterrupt | LocalApicRegisterIndex::SelfIpi)
+    }
+
+    pub fn is_writable(self) -> bool {
+        !matches!(self,
+            LocalApicRegisterIndex::Version |
+            LocalApicRegisterIndex::ArbitrationPriority |
+            LocalApicRegisterIndex::ProcessorPriority |
+            LocalApicRegisterIndex::RemoteRead |
+            LocalApicRegisterIndex::TimerCurrentCount) &&
+            !LocalApicRegisterIndex::IN_SERVICE.contains(&self) &&
+            !LocalApicRegisterIndex::TRIGGER_MODE.contains(&self) &&
+            !LocalApicRegisterIndex::INTERRUPT_REQUEST.contains(&self)
+    }
+
     pub fn as_u64(self) -> u64 {
         u64::from(self.as_u32())
     }
@@ -64,7 +117,16 @@ impl LocalApicRegisterIndex {
 
 pub trait LocalApicRegister {
     type Value;
+}
+
+pub trait ReadableLocalApicRegister: LocalApicRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError>;
+}
+
+pub trait WritableLocalApicRegister: LocalApicRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError>;
+}
+
+pub trait ReadWriteLocalApicRegister: ReadableLocalApicRegister + WritableLocalApicRegister {}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value;
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value);
-}
\ No newline at end of file
+impl<R: ReadableLocalApicRegister + WritableLocalApicRegister> ReadWriteLocalApicRegister for R {}
\ No newline at end of file
@@ -1,12 +1,13 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex, InterruptVector};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex, InterruptVector};
 
 bitflags! {
     pub struct SivrFlags: u32 {
-        const VECTOR                   = 0b0000_0000_0000_11111111;
-        const APIC_ENABLE              = 0b0000_0000_0001_00000000;
-        const FOCUS_PROCESSOR_CHECKING = 0b0000_0000_0010_00000000;
-        const EOI_BROADCAST_SUPRESSION = 0b0000_0001_0000_00000000;
-        const UNUSED                   = 0b1111_1110_1100_00000000;
+        const VECTOR                   = 0b0000_0000_0000_1111_1111;
+        const APIC_ENABLE              = 0b0000_0000_0001_0000_0000;
+        const FOCUS_PROCESSOR_CHECKING = 0b0000_0000_0010_0000_0000;
+        const EOI_BROADCAST_SUPRESSION = 0b0000_0001_0000_0000_0000;
+        const UNUSED                   = 0b1111_1110_1100_0000_0000;
     }
 }
 
@@ -31,12 +32,16 @@ impl SivrFlags {
 pub struct SpuriousInterruptVectorRegister;
 impl LocalApicRegister for SpuriousInterruptVectorRegister {
     type Value = SivrFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::SpuriousInterrupt))
+impl ReadableLocalApicRegister for SpuriousInterruptVectorRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::SpuriousInterrupt)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::SpuriousInterrupt, value.bits());
+impl WritableLocalApicRegister for SpuriousInterruptVectorRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::SpuriousInterrupt, value.bits())
     }
 }
\ No newline at end of file
@@ -0,0 +1,253 @@
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::local::{
+    LocalApic, ReadableLocalApicRegister, WritableLocalApicRegister, ApicId, ApicMode, InterruptCommandRegister, ErrorStatusRegister, ErrorStatusFlags,
+    Ipi, IpiDestination, X2ApicId,
+};
+
+const INIT_DEASSERT_DELAY_US: u32 = 10_000;
+const STARTUP_DELAY_US: u32 = 200;
+const POLL_INTERVAL_US: u32 = 10;
+const POLL_ATTEMPTS: u32 = 1_000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StartupError {
+    InvalidTrampoline(u32),
+    InvalidDestination(StartupTarget),
+    InvalidIpi(&'static str),
+    DeliveryTimeout,
+    SendError(ErrorStatusFlags),
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for StartupError {
+    fn from(error: RegisterError) -> Self {
+        StartupError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StartupTarget {
+    XApic(ApicId),
+    X2Apic(X2ApicId),
+}
+
+impl From<ApicId> for StartupTarget {
+    fn from(id: ApicId) -> Self {
+        StartupTarget::XApic(id)
+    }
+}
+
+impl From<X2ApicId> for StartupTarget {
+    fn from(id: X2ApicId) -> Self {
+        StartupTarget::X2Apic(id)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrampolinePage(u8);
+
+impl TrampolinePage {
+    pub fn new(address: u32) -> Result<Self, StartupError> {
+        if address & 0xfff != 0 || address >= 0x10_0000 {
+            Err(StartupError::InvalidTrampoline(address))
+        } else {
+            Ok(TrampolinePage((address >> 12) as u8))
+        }
+    }
+
+    pub fn page(&self) -> u8 {
+        self.0
+    }
+
+    pub fn address(&self) -> u32 {
+        (self.0 as u32) << 12
+    }
+}
+
+pub unsafe fn start_application_processor<T: Into<StartupTarget>, F: FnMut(u32)>(
+    apic: &dyn LocalApic,
+    target: T,
+    trampoline: TrampolinePage,
+    mut delay_us: F,
+) -> Result<(), StartupError> {
+    let target = target.into();
+    let destination = match target {
+        StartupTarget::XApic(ApicId::Id4Bit(id)) if id <= 0xf => IpiDestination::Physical(id),
+        StartupTarget::XApic(ApicId::Id8Bit(id)) if id <= 0xff => IpiDestination::Physical(id),
+        StartupTarget::X2Apic(id) if id.0 <= 0xff || apic.mode() == ApicMode::X2Apic => IpiDestination::Physical(id.0),
+        _ => return Err(StartupError::InvalidDestination(target)),
+    };
+
+    clear_errors(apic)?;
+
+    send(apic, Ipi::init_assert(destination), &mut delay_us)?;
+    send(apic, Ipi::init_deassert(destination), &mut delay_us)?;
+    delay_us(INIT_DEASSERT_DELAY_US);
+
+    for _ in 0..2 {
+        send(apic, Ipi::startup(trampoline.page(), destination), &mut delay_us)?;
+        delay_us(STARTUP_DELAY_US);
+    }
+
+    Ok(())
+}
+
+unsafe fn send<F: FnMut(u32)>(apic: &dyn LocalApic, ipi: Ipi, delay_us: &mut F) -> Result<(), StartupError> {
+    let command = match apic.mode() {
+        ApicMode::X2Apic => ipi.to_x2apic_flags(),
+        _ => ipi.to_flags(),
+    }.map_err(StartupError::InvalidIpi)?;
+    InterruptCommandRegister.write(apic, command)?;
+
+    wait_for_delivery(apic, delay_us)?;
+    check_errors(apic)
+}
+
+unsafe fn wait_for_delivery<F: FnMut(u32)>(apic: &dyn LocalApic, delay_us: &mut F) -> Result<(), StartupError> {
+    for _ in 0..POLL_ATTEMPTS {
+        if !InterruptCommandRegister.read(apic)?.is_send_pending() {
+            return Ok(());
+        }
+        delay_us(POLL_INTERVAL_US);
+    }
+
+    Err(StartupError::DeliveryTimeout)
+}
+
+unsafe fn clear_errors(apic: &dyn LocalApic) -> Result<(), StartupError> {
+    // a write latches the pending errors into the readable register
+    ErrorStatusRegister.write(apic, ErrorStatusFlags::empty())?;
+    ErrorStatusRegister.write(apic, ErrorStatusFlags::empty())?;
+    Ok(())
+}
+
+unsafe fn check_errors(apic: &dyn LocalApic) -> Result<(), StartupError> {
+    let send_errors = ErrorStatusFlags::SEND_CHECKSUM_ERROR
+        | ErrorStatusFlags::SEND_ACCEPT_ERROR
+        | ErrorStatusFlags::SEND_ILLEGAL_VECTOR;
+
+    ErrorStatusRegister.write(apic, ErrorStatusFlags::empty())?;
+    let errors = ErrorStatusRegister.read(apic)? & send_errors;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(StartupError::SendError(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use crate::local::{LocalApicRegisterIndex, InterruptCommandFlags, IcrDeliveryMode, X2Apic};
+    use crate::msr::FakeMsr;
+
+    struct FakeApic {
+        sent: RefCell<Vec<InterruptCommandFlags>>,
+        icr: RefCell<u64>,
+        busy: bool,
+        errors: u32,
+    }
+
+    impl FakeApic {
+        fn new(busy: bool, errors: u32) -> Self {
+            FakeApic { sent: RefCell::new(Vec::new()), icr: RefCell::new(0), busy, errors }
+        }
+    }
+
+    impl LocalApic for FakeApic {
+        unsafe fn read_reg_32(&self, index: LocalApicRegisterIndex) -> u32 {
+            match index {
+                LocalApicRegisterIndex::InterruptCommand0 if self.busy => *self.icr.borrow() as u32 | 0x1000,
+                LocalApicRegisterIndex::InterruptCommand0 => *self.icr.borrow() as u32,
+                LocalApicRegisterIndex::InterruptCommand1 => (*self.icr.borrow() >> 32) as u32,
+                LocalApicRegisterIndex::ErrorStatus => self.errors,
+                _ => 0,
+            }
+        }
+
+        unsafe fn write_reg_32(&self, index: LocalApicRegisterIndex, value: u32) {
+            let mut icr = self.icr.borrow_mut();
+            match index {
+                LocalApicRegisterIndex::InterruptCommand1 => *icr = (*icr & 0xffff_ffff) | ((value as u64) << 32),
+                LocalApicRegisterIndex::InterruptCommand0 => {
+                    *icr = (*icr & 0xffff_ffff_0000_0000) | value as u64;
+                    self.sent.borrow_mut().push(InterruptCommandFlags::from_bits_truncate(*icr));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_trampoline_page() {
+        assert_eq!(TrampolinePage::new(0x8000).map(|page| page.page()), Ok(0x08));
+        assert_eq!(TrampolinePage::new(0x8010), Err(StartupError::InvalidTrampoline(0x8010)));
+        assert_eq!(TrampolinePage::new(0x10_0000), Err(StartupError::InvalidTrampoline(0x10_0000)));
+    }
+
+    #[test]
+    pub fn test_init_sipi_sipi() {
+        let apic = FakeApic::new(false, 0);
+        let mut delays = Vec::new();
+
+        let result = unsafe {
+            start_application_processor(&apic, ApicId::Id8Bit(2), TrampolinePage::new(0x8000).unwrap(), |us| delays.push(us))
+        };
+        assert_eq!(result, Ok(()));
+
+        let sent = apic.sent.borrow();
+        let modes: Vec<IcrDeliveryMode> = sent.iter().map(|command| command.delivery_mode().unwrap()).collect();
+        assert_eq!(modes, vec![IcrDeliveryMode::INIT, IcrDeliveryMode::INIT, IcrDeliveryMode::StartUp, IcrDeliveryMode::StartUp]);
+        assert!(sent.iter().all(|command| command.destination() == 2));
+        assert_eq!(sent[3].vector().0, 0x08);
+        assert_eq!(delays, vec![10_000, 200, 200]);
+    }
+
+    #[test]
+    pub fn test_x2apic_destination() {
+        let apic = X2Apic::new(FakeMsr::default());
+
+        let result = unsafe {
+            start_application_processor(&apic, X2ApicId(0x1_0002), TrampolinePage::new(0x8000).unwrap(), |_| {})
+        };
+        assert_eq!(result, Ok(()));
+
+        let commands: Vec<InterruptCommandFlags> = apic.msr().writes().iter()
+            .filter(|(msr, _)| *msr == 0x830)
+            .map(|(_, value)| InterruptCommandFlags::from_bits_truncate(*value))
+            .collect();
+        assert_eq!(commands.len(), 4);
+        assert!(commands.iter().all(|command| command.x2apic_destination() == 0x1_0002));
+
+        let xapic = FakeApic::new(false, 0);
+        let result = unsafe {
+            start_application_processor(&xapic, X2ApicId(0x100), TrampolinePage::new(0x8000).unwrap(), |_| {})
+        };
+        assert_eq!(result, Err(StartupError::InvalidDestination(StartupTarget::X2Apic(X2ApicId(0x100)))));
+        assert!(xapic.sent.borrow().is_empty());
+    }
+
+    #[test]
+    pub fn test_delivery_timeout() {
+        let apic = FakeApic::new(true, 0);
+
+        let result = unsafe {
+            start_application_processor(&apic, ApicId::Id8Bit(2), TrampolinePage::new(0x8000).unwrap(), |_| {})
+        };
+        assert_eq!(result, Err(StartupError::DeliveryTimeout));
+        assert_eq!(apic.sent.borrow().len(), 1);
+    }
+
+    #[test]
+    pub fn test_send_accept_error() {
+        let apic = FakeApic::new(false, ErrorStatusFlags::SEND_ACCEPT_ERROR.bits());
+
+        let result = unsafe {
+            start_application_processor(&apic, ApicId::Id8Bit(2), TrampolinePage::new(0x8000).unwrap(), |_| {})
+        };
+        assert_eq!(result, Err(StartupError::SendError(ErrorStatusFlags::SEND_ACCEPT_ERROR)));
+    }
+}
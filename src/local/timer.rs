@@ -1,6 +1,7 @@
-use core::u8;
 use core::convert::TryFrom;
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use crate::msr::Msr;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct TimerDivideConfigurationFlags: u32 {
@@ -20,7 +21,7 @@ impl From<TimerDivideConfigurationFlags> for LvtTimerDivideValue {
         let b = (flags & TimerDivideConfigurationFlags::DIVIDE_BITS_3).bits();
         let c = (b >> 1) | a; // 3 contiguous bits
 
-        let divisor = (2 as u8).rotate_left(c);
+        let divisor = 2u8.rotate_left(c);
 
         LvtTimerDivideValue(divisor as u32)
     }
@@ -49,13 +50,17 @@ impl TryFrom<LvtTimerDivideValue> for TimerDivideConfigurationFlags {
 pub struct LvtTimerDivideConfigurationRegister;
 impl LocalApicRegister for LvtTimerDivideConfigurationRegister {
     type Value = TimerDivideConfigurationFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::TimerDivideConfiguration))
+impl ReadableLocalApicRegister for LvtTimerDivideConfigurationRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::TimerDivideConfiguration)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::TimerDivideConfiguration, TimerDivideConfigurationFlags::from(value).bits());
+impl WritableLocalApicRegister for LvtTimerDivideConfigurationRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::TimerDivideConfiguration, value.bits())
     }
 }
 
@@ -64,13 +69,17 @@ pub struct LvtTimerInitialCount(pub u32);
 pub struct LvtTimerInitialCountRegister;
 impl LocalApicRegister for LvtTimerInitialCountRegister {
     type Value = LvtTimerInitialCount;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        LvtTimerInitialCount(apic.read_reg_32(LocalApicRegisterIndex::TimerInitialCount))
+impl ReadableLocalApicRegister for LvtTimerInitialCountRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(LvtTimerInitialCount(apic.try_read_reg_32(LocalApicRegisterIndex::TimerInitialCount)?))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::TimerInitialCount, value.0);
+impl WritableLocalApicRegister for LvtTimerInitialCountRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::TimerInitialCount, value.0)
     }
 }
 
@@ -79,13 +88,27 @@ pub struct LvtTimerCurrentCount(pub u32);
 pub struct LvtTimerCurrentCountRegister;
 impl LocalApicRegister for LvtTimerCurrentCountRegister {
     type Value = LvtTimerCurrentCount;
+}
+
+impl ReadableLocalApicRegister for LvtTimerCurrentCountRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(LvtTimerCurrentCount(apic.try_read_reg_32(LocalApicRegisterIndex::TimerCurrentCount)?))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TscDeadline(pub u64);
+pub struct TscDeadlineRegister;
+
+impl TscDeadlineRegister {
+    pub const MSR: u32 = 0x6e0;
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        LvtTimerCurrentCount(apic.read_reg_32(LocalApicRegisterIndex::TimerCurrentCount))
+    pub unsafe fn read(&self, msr: &dyn Msr) -> TscDeadline {
+        TscDeadline(msr.read_msr(Self::MSR))
     }
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::TimerCurrentCount, value.0);
+    pub unsafe fn write(&self, msr: &dyn Msr, value: TscDeadline) {
+        msr.write_msr(Self::MSR, value.0);
     }
 }
 
@@ -1,3 +1,18 @@
-pub struct TriggerModeRegister {
-    // 8 x 32 bits starting at fee00180h ending at fee001f0h
-}
\ No newline at end of file
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex, InterruptVectorSet};
+
+pub struct TriggerModeRegister;
+impl LocalApicRegister for TriggerModeRegister {
+    type Value = InterruptVectorSet;
+}
+
+impl ReadableLocalApicRegister for TriggerModeRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let mut words = [0; 8];
+        for (word, index) in words.iter_mut().zip(LocalApicRegisterIndex::TRIGGER_MODE.iter()) {
+            *word = apic.try_read_reg_32(*index)?;
+        }
+
+        Ok(InterruptVectorSet::from_words(words))
+    }
+}
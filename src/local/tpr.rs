@@ -1,4 +1,5 @@
-use crate::local::{LocalApic, LocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
+use crate::error::RegisterError;
+use crate::local::{LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex, PriorityClass, PrioritySubClass};
 
 bitflags! {
     pub struct TaskPriorityFlags: u32 {
@@ -33,13 +34,18 @@ impl From<PrioritySubClass> for TaskPriorityFlags {
 pub struct TaskPriorityRegister;
 impl LocalApicRegister for TaskPriorityRegister {
     type Value = TaskPriorityFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        TaskPriorityFlags::from_bits(apic.read_reg_32(LocalApicRegisterIndex::TaskPriority)).unwrap()
+impl ReadableLocalApicRegister for TaskPriorityRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        let value = apic.try_read_reg_32(LocalApicRegisterIndex::TaskPriority)?;
+        TaskPriorityFlags::from_bits(value).ok_or(RegisterError::UndefinedBits(value as u64))
     }
+}
 
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::TaskPriority, TaskPriorityFlags::from(value).bits());
+impl WritableLocalApicRegister for TaskPriorityRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::TaskPriority, value.bits())
     }
 }
 
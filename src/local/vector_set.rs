@@ -0,0 +1,167 @@
+use crate::local::{InterruptVector, PriorityClass};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterruptVectorSet([u32; 8]);
+
+impl InterruptVectorSet {
+    pub const fn new() -> Self {
+        InterruptVectorSet([0; 8])
+    }
+
+    pub const fn from_words(words: [u32; 8]) -> Self {
+        InterruptVectorSet(words)
+    }
+
+    pub fn words(&self) -> [u32; 8] {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn contains(&self, vector: InterruptVector) -> bool {
+        match Self::position(vector) {
+            Some((word, bit)) => self.0[word] & (1 << bit) != 0,
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, vector: InterruptVector) {
+        if let Some((word, bit)) = Self::position(vector) {
+            self.0[word] |= 1 << bit;
+        }
+    }
+
+    pub fn remove(&mut self, vector: InterruptVector) {
+        if let Some((word, bit)) = Self::position(vector) {
+            self.0[word] &= !(1 << bit);
+        }
+    }
+
+    pub fn highest(&self) -> Option<InterruptVector> {
+        self.0.iter().enumerate().rev()
+            .find(|(_, word)| **word != 0)
+            .map(|(index, word)| InterruptVector(index as u32 * 32 + 31 - word.leading_zeros()))
+    }
+
+    pub fn lowest(&self) -> Option<InterruptVector> {
+        self.iter().next()
+    }
+
+    pub fn iter(&self) -> InterruptVectorSetIter {
+        InterruptVectorSetIter { words: self.0, word: 0 }
+    }
+
+    pub fn priority_class(&self, class: PriorityClass) -> u16 {
+        if class.0 > 0xf {
+            return 0;
+        }
+
+        let word = self.0[class.0 as usize / 2];
+        (word >> ((class.0 % 2) * 16)) as u16
+    }
+
+    pub fn highest_priority_class(&self) -> Option<PriorityClass> {
+        self.highest().map(|vector| vector.priority_class())
+    }
+
+    fn position(vector: InterruptVector) -> Option<(usize, u32)> {
+        if vector.0 > 0xff {
+            None
+        } else {
+            Some(((vector.0 / 32) as usize, vector.0 % 32))
+        }
+    }
+}
+
+impl IntoIterator for InterruptVectorSet {
+    type Item = InterruptVector;
+    type IntoIter = InterruptVectorSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &InterruptVectorSet {
+    type Item = InterruptVector;
+    type IntoIter = InterruptVectorSetIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct InterruptVectorSetIter {
+    words: [u32; 8],
+    word: usize,
+}
+
+impl Iterator for InterruptVectorSetIter {
+    type Item = InterruptVector;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.word < self.words.len() {
+            let bits = self.words[self.word];
+            if bits != 0 {
+                let bit = bits.trailing_zeros();
+                self.words[self.word] &= !(1 << bit);
+                return Some(InterruptVector(self.word as u32 * 32 + bit));
+            }
+            self.word += 1;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_insert_contains() {
+        let mut set = InterruptVectorSet::new();
+        assert!(set.is_empty());
+
+        set.insert(InterruptVector(0x20));
+        set.insert(InterruptVector(0xff));
+        assert!(set.contains(InterruptVector(0x20)));
+        assert!(set.contains(InterruptVector(0xff)));
+        assert!(!set.contains(InterruptVector(0x21)));
+        assert!(!set.contains(InterruptVector(0x100)));
+        assert_eq!(set.len(), 2);
+
+        set.remove(InterruptVector(0x20));
+        assert!(!set.contains(InterruptVector(0x20)));
+    }
+
+    #[test]
+    pub fn test_iter_and_highest() {
+        let set = InterruptVectorSet::from_words([0x1, 0, 0x8000_0000, 0, 0, 0x10, 0, 0]);
+        let vectors: Vec<InterruptVector> = set.iter().collect();
+
+        assert_eq!(vectors, vec![InterruptVector(0x00), InterruptVector(0x5f), InterruptVector(0xa4)]);
+        assert_eq!(set.highest(), Some(InterruptVector(0xa4)));
+        assert_eq!(set.lowest(), Some(InterruptVector(0x00)));
+        assert_eq!(InterruptVectorSet::new().highest(), None);
+    }
+
+    #[test]
+    pub fn test_priority_class() {
+        let mut set = InterruptVectorSet::new();
+        set.insert(InterruptVector(0x31));
+        set.insert(InterruptVector(0x3f));
+        set.insert(InterruptVector(0x40));
+
+        assert_eq!(set.priority_class(PriorityClass(3)), 0x8002);
+        assert_eq!(set.priority_class(PriorityClass(4)), 0x0001);
+        assert_eq!(set.priority_class(PriorityClass(2)), 0);
+        assert_eq!(set.highest_priority_class(), Some(PriorityClass(4)));
+    }
+}
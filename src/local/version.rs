@@ -1,5 +1,6 @@
 use super::LocalApic;
-use super::registers::{LocalApicRegister, LocalApicRegisterIndex};
+use crate::error::RegisterError;
+use super::registers::{LocalApicRegister, ReadableLocalApicRegister, LocalApicRegisterIndex};
 
 bitflags! {
     pub struct VersionFlags: u32 {
@@ -28,12 +29,10 @@ impl VersionFlags {
 pub struct VersionRegister;
 impl LocalApicRegister for VersionRegister {
     type Value = VersionFlags;
+}
 
-    unsafe fn read(&self, apic: &dyn LocalApic) -> Self::Value {
-        Self::Value::from_bits_truncate(apic.read_reg_32(LocalApicRegisterIndex::Version))
-    }
-
-    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) {
-        apic.write_reg_32(LocalApicRegisterIndex::Version, value.bits());
+impl ReadableLocalApicRegister for VersionRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(Self::Value::from_bits_truncate(apic.try_read_reg_32(LocalApicRegisterIndex::Version)?))
     }
 }
@@ -0,0 +1,335 @@
+use core::cell::Cell;
+use core::convert::TryFrom;
+use core::result::Result;
+use crate::msr::Msr;
+use crate::error::RegisterError;
+use crate::local::{ApicMode, LocalApic, LocalApicRegister, ReadableLocalApicRegister, WritableLocalApicRegister, LocalApicRegisterIndex, InterruptVector, Ipi, IpiDestination};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct X2ApicMsr(pub u32);
+
+impl X2ApicMsr {
+    pub const BASE: X2ApicMsr = X2ApicMsr(0x800);
+    pub const ID: X2ApicMsr = X2ApicMsr(0x802);
+    pub const LOGICAL_DESTINATION: X2ApicMsr = X2ApicMsr(0x80d);
+    pub const INTERRUPT_COMMAND: X2ApicMsr = X2ApicMsr(0x830);
+    pub const SELF_IPI: X2ApicMsr = X2ApicMsr(0x83f);
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl TryFrom<LocalApicRegisterIndex> for X2ApicMsr {
+    type Error = &'static str;
+
+    fn try_from(index: LocalApicRegisterIndex) -> Result<Self, Self::Error> {
+        match index {
+            LocalApicRegisterIndex::ArbitrationPriority => Err("arbitration priority is not available in x2apic mode"),
+            LocalApicRegisterIndex::RemoteRead => Err("remote read is not available in x2apic mode"),
+            LocalApicRegisterIndex::DestinationFormat => Err("destination format is not available in x2apic mode"),
+            LocalApicRegisterIndex::InterruptCommand1 => Err("interrupt command is a single 64 bit msr in x2apic mode"),
+            _ => Ok(X2ApicMsr(X2ApicMsr::BASE.0 + (index.as_u32() >> 4))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct X2ApicId(pub u32);
+
+pub struct X2ApicIdRegister;
+impl LocalApicRegister for X2ApicIdRegister {
+    type Value = X2ApicId;
+}
+
+impl ReadableLocalApicRegister for X2ApicIdRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(X2ApicId(apic.try_read_reg_32(LocalApicRegisterIndex::Id)?))
+    }
+}
+
+impl X2ApicId {
+    pub const MAX_LOGICAL: X2ApicId = X2ApicId(0x000f_ffff);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum X2ApicLogicalError {
+    Unaddressable(X2ApicId),
+    TooManyClusters,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct X2ApicLogicalId(pub u32);
+
+impl X2ApicLogicalId {
+    pub fn new(cluster: u16, members: u16) -> Self {
+        X2ApicLogicalId(((cluster as u32) << 16) | members as u32)
+    }
+
+    pub fn cluster_id(&self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    pub fn members(&self) -> u16 {
+        self.0 as u16
+    }
+
+    pub fn contains(&self, id: X2ApicId) -> bool {
+        X2ApicLogicalId::try_from(id)
+            .map(|logical| logical.cluster_id() == self.cluster_id() && logical.members() & self.members() != 0)
+            .unwrap_or(false)
+    }
+}
+
+impl TryFrom<X2ApicId> for X2ApicLogicalId {
+    type Error = X2ApicLogicalError;
+
+    fn try_from(id: X2ApicId) -> Result<Self, Self::Error> {
+        if id > X2ApicId::MAX_LOGICAL {
+            return Err(X2ApicLogicalError::Unaddressable(id));
+        }
+
+        Ok(X2ApicLogicalId::new((id.0 >> 4) as u16, 1 << (id.0 & 0xf)))
+    }
+}
+
+impl From<X2ApicLogicalId> for IpiDestination {
+    fn from(id: X2ApicLogicalId) -> Self {
+        IpiDestination::Logical(id.0)
+    }
+}
+
+pub struct X2ApicLogicalDestinationRegister;
+impl LocalApicRegister for X2ApicLogicalDestinationRegister {
+    type Value = X2ApicLogicalId;
+}
+
+impl ReadableLocalApicRegister for X2ApicLogicalDestinationRegister {
+    unsafe fn read(&self, apic: &dyn LocalApic) -> Result<Self::Value, RegisterError> {
+        Ok(X2ApicLogicalId(apic.try_read_reg_32(LocalApicRegisterIndex::LogicalDestination)?))
+    }
+}
+
+pub struct X2ApicMulticast<const N: usize> {
+    destinations: [Option<X2ApicLogicalId>; N],
+}
+
+impl<const N: usize> X2ApicMulticast<N> {
+    pub fn new() -> Self {
+        X2ApicMulticast { destinations: [None; N] }
+    }
+
+    pub fn add(&mut self, id: X2ApicId) -> Result<(), X2ApicLogicalError> {
+        let logical = X2ApicLogicalId::try_from(id)?;
+
+        for slot in self.destinations.iter_mut() {
+            match slot {
+                Some(existing) if existing.cluster_id() == logical.cluster_id() => {
+                    *existing = X2ApicLogicalId(existing.0 | logical.0);
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => {
+                    *slot = Some(logical);
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(X2ApicLogicalError::TooManyClusters)
+    }
+
+    pub fn destinations(&self) -> impl Iterator<Item = X2ApicLogicalId> + '_ {
+        self.destinations.iter().flatten().copied()
+    }
+
+    pub fn ipis(&self, vector: InterruptVector) -> impl Iterator<Item = Ipi> + '_ {
+        self.destinations().map(move |destination| Ipi::fixed(vector, destination.into()))
+    }
+}
+
+impl<const N: usize> Default for X2ApicMulticast<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct SelfIpiRegister;
+impl LocalApicRegister for SelfIpiRegister {
+    type Value = InterruptVector;
+}
+
+impl WritableLocalApicRegister for SelfIpiRegister {
+    unsafe fn write(&self, apic: &dyn LocalApic, value: Self::Value) -> Result<(), RegisterError> {
+        apic.try_write_reg_32(LocalApicRegisterIndex::SelfIpi, value.0 & 0xff)
+    }
+}
+
+pub struct X2Apic<M: Msr> {
+    msr: M,
+    icr_high: Cell<u32>,
+}
+
+impl<M: Msr> X2Apic<M> {
+    pub fn new(msr: M) -> Self {
+        X2Apic {
+            msr,
+            icr_high: Cell::new(0),
+        }
+    }
+
+    pub fn msr(&self) -> &M {
+        &self.msr
+    }
+
+    pub unsafe fn read_icr(&self) -> u64 {
+        self.msr.read_msr(X2ApicMsr::INTERRUPT_COMMAND.0)
+    }
+
+    pub unsafe fn write_icr(&self, value: u64) {
+        self.msr.write_msr(X2ApicMsr::INTERRUPT_COMMAND.0, value);
+    }
+}
+
+impl<M: Msr> LocalApic for X2Apic<M> {
+    unsafe fn read_reg_32(&self, index: LocalApicRegisterIndex) -> u32 {
+        match index {
+            LocalApicRegisterIndex::InterruptCommand0 => self.read_icr() as u32,
+            LocalApicRegisterIndex::InterruptCommand1 => (self.read_icr() >> 32) as u32,
+            // unsupported registers read as zero, see supports()
+            _ => X2ApicMsr::try_from(index).map_or(0, |msr| self.msr.read_msr(msr.0) as u32),
+        }
+    }
+
+    unsafe fn write_reg_32(&self, index: LocalApicRegisterIndex, value: u32) {
+        match index {
+            // the high dword is latched until the low dword write sends the ipi
+            LocalApicRegisterIndex::InterruptCommand1 => self.icr_high.set(value),
+            LocalApicRegisterIndex::InterruptCommand0 => {
+                self.write_icr(((self.icr_high.get() as u64) << 32) | value as u64)
+            }
+            _ => {
+                if let Ok(msr) = X2ApicMsr::try_from(index) {
+                    self.msr.write_msr(msr.0, value as u64);
+                }
+            }
+        }
+    }
+
+    fn mode(&self) -> ApicMode {
+        ApicMode::X2Apic
+    }
+
+    fn supports(&self, index: LocalApicRegisterIndex) -> bool {
+        !matches!(index,
+            LocalApicRegisterIndex::ArbitrationPriority |
+            LocalApicRegisterIndex::RemoteRead |
+            LocalApicRegisterIndex::DestinationFormat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msr::FakeMsr;
+    use crate::local::{
+        ArbitrationPriorityRegister, DestinationFormatFlags, DestinationFormatRegister, InterruptCommandRegister,
+        InterruptCommandFlags,
+    };
+
+    #[test]
+    pub fn test_logical_id() {
+        let logical = X2ApicLogicalId::try_from(X2ApicId(0x1234)).unwrap();
+
+        assert_eq!(logical, X2ApicLogicalId(0x0123_0010));
+        assert_eq!(logical.cluster_id(), 0x123);
+        assert!(logical.contains(X2ApicId(0x1234)));
+        assert!(!logical.contains(X2ApicId(0x1235)));
+        assert_eq!(X2ApicLogicalId::try_from(X2ApicId(0x10_0000)), Err(X2ApicLogicalError::Unaddressable(X2ApicId(0x10_0000))));
+
+        let apic = X2Apic::new(FakeMsr::default());
+        unsafe {
+            apic.msr().write_msr(X2ApicMsr::LOGICAL_DESTINATION.0, 0x0123_0010);
+            assert_eq!(X2ApicLogicalDestinationRegister.read(&apic).unwrap(), logical);
+        }
+    }
+
+    #[test]
+    pub fn test_multicast() {
+        let mut multicast = X2ApicMulticast::<2>::new();
+        for id in [0x00, 0x03, 0x12, 0x1f, 0x01].iter() {
+            multicast.add(X2ApicId(*id)).unwrap();
+        }
+        assert_eq!(multicast.add(X2ApicId(0x20)), Err(X2ApicLogicalError::TooManyClusters));
+
+        assert!(multicast.destinations().eq([X2ApicLogicalId(0x0000_000b), X2ApicLogicalId(0x0001_8004)].iter().copied()));
+
+        let icrs: std::vec::Vec<u64> = multicast.ipis(InterruptVector(0x40))
+            .map(|ipi| ipi.to_x2apic_flags().unwrap().bits())
+            .collect();
+        assert_eq!(icrs, [0x0000_000b_0000_4840, 0x0001_8004_0000_4840]);
+        assert_eq!(InterruptCommandFlags::from_bits_truncate(icrs[1]).x2apic_destination(), 0x0001_8004);
+    }
+
+    #[test]
+    pub fn test_msr_mapping() {
+        assert_eq!(X2ApicMsr::try_from(LocalApicRegisterIndex::Id), Ok(X2ApicMsr::ID));
+        assert_eq!(X2ApicMsr::try_from(LocalApicRegisterIndex::LogicalDestination), Ok(X2ApicMsr::LOGICAL_DESTINATION));
+        assert_eq!(X2ApicMsr::try_from(LocalApicRegisterIndex::InterruptCommand0), Ok(X2ApicMsr::INTERRUPT_COMMAND));
+        assert_eq!(X2ApicMsr::try_from(LocalApicRegisterIndex::SelfIpi), Ok(X2ApicMsr::SELF_IPI));
+        assert_eq!(X2ApicMsr::try_from(LocalApicRegisterIndex::TimerDivideConfiguration), Ok(X2ApicMsr(0x83e)));
+
+        assert!(X2ApicMsr::try_from(LocalApicRegisterIndex::DestinationFormat).is_err());
+        assert!(X2ApicMsr::try_from(LocalApicRegisterIndex::ArbitrationPriority).is_err());
+        assert!(X2ApicMsr::try_from(LocalApicRegisterIndex::RemoteRead).is_err());
+        assert!(X2ApicMsr::try_from(LocalApicRegisterIndex::InterruptCommand1).is_err());
+    }
+
+    #[test]
+    pub fn test_unsupported_registers() {
+        let apic = X2Apic::new(FakeMsr::default());
+        let unsupported = [
+            LocalApicRegisterIndex::DestinationFormat,
+            LocalApicRegisterIndex::ArbitrationPriority,
+            LocalApicRegisterIndex::RemoteRead,
+        ];
+
+        unsafe {
+            let dyn_apic: &dyn LocalApic = &apic;
+            for index in unsupported.iter() {
+                assert!(!apic.supports(*index));
+                assert_eq!(dyn_apic.try_read_reg_32(*index), Err(RegisterError::Unsupported(*index)));
+                assert_eq!(dyn_apic.try_write_reg_32(*index, 0), Err(RegisterError::Unsupported(*index)));
+                assert_eq!(apic.read_reg_32(*index), 0);
+            }
+
+            assert_eq!(DestinationFormatRegister.read(&apic), Err(RegisterError::Unsupported(LocalApicRegisterIndex::DestinationFormat)));
+            assert_eq!(DestinationFormatRegister.write(&apic, DestinationFormatFlags::flat()),
+                Err(RegisterError::Unsupported(LocalApicRegisterIndex::DestinationFormat)));
+            assert_eq!(ArbitrationPriorityRegister.read(&apic), Err(RegisterError::Unsupported(LocalApicRegisterIndex::ArbitrationPriority)));
+            assert!(apic.msr().writes().is_empty());
+        }
+    }
+
+    #[test]
+    pub fn test_icr_is_single_write() {
+        let apic = X2Apic::new(FakeMsr::default());
+        let value = InterruptCommandFlags::from_bits_truncate(0x0000_0123_0000_4030);
+
+        unsafe {
+            InterruptCommandRegister.write(&apic, value).unwrap();
+            assert_eq!(apic.msr().read_msr(0x830), 0x0000_0123_0000_4030);
+            assert_eq!(InterruptCommandRegister.read(&apic).unwrap(), value);
+        }
+    }
+
+    #[test]
+    pub fn test_x2apic_id() {
+        let apic = X2Apic::new(FakeMsr::default());
+
+        unsafe {
+            apic.msr().write_msr(0x802, 0x1234);
+            assert_eq!(X2ApicIdRegister.read(&apic).unwrap(), X2ApicId(0x1234));
+        }
+    }
+}
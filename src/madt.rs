@@ -0,0 +1,435 @@
+use core::convert::TryFrom;
+use core::result::Result;
+use crate::io::{self, Polarity, TriggerMode};
+use crate::local::{self, X2ApicId};
+
+const HEADER_LENGTH: usize = 36;
+const ENTRIES_OFFSET: usize = 44;
+
+bitflags! {
+    pub struct MadtFlags: u32 {
+        const PCAT_COMPAT = 0x0000_0001;
+    }
+}
+
+bitflags! {
+    pub struct LocalApicFlags: u32 {
+        const ENABLED        = 0x0000_0001;
+        const ONLINE_CAPABLE = 0x0000_0002;
+    }
+}
+
+impl LocalApicFlags {
+    pub fn is_usable(&self) -> bool {
+        self.intersects(LocalApicFlags::ENABLED | LocalApicFlags::ONLINE_CAPABLE)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MpsIntiFlags {
+    pub polarity: Option<Polarity>,
+    pub trigger_mode: Option<TriggerMode>,
+}
+
+impl MpsIntiFlags {
+    pub fn polarity_or(&self, default: Polarity) -> Polarity {
+        self.polarity.unwrap_or(default)
+    }
+
+    pub fn trigger_mode_or(&self, default: TriggerMode) -> TriggerMode {
+        self.trigger_mode.unwrap_or(default)
+    }
+}
+
+impl TryFrom<u16> for MpsIntiFlags {
+    type Error = MadtError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let polarity = match value & 0x3 {
+            0x0 => None,
+            0x1 => Some(Polarity::ActiveHigh),
+            0x3 => Some(Polarity::ActiveLow),
+            _ => return Err(MadtError::ReservedIntiFlags(value)),
+        };
+
+        let trigger_mode = match (value >> 2) & 0x3 {
+            0x0 => None,
+            0x1 => Some(TriggerMode::Edge),
+            0x3 => Some(TriggerMode::Level),
+            _ => return Err(MadtError::ReservedIntiFlags(value)),
+        };
+
+        Ok(MpsIntiFlags { polarity, trigger_mode })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MadtError {
+    InvalidSignature,
+    InvalidLength,
+    InvalidChecksum,
+    TruncatedEntry { offset: usize },
+    ReservedIntiFlags(u16),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MadtEntry<'a> {
+    LocalApic {
+        processor_uid: u8,
+        apic_id: local::ApicId,
+        flags: LocalApicFlags,
+    },
+    IoApic {
+        id: io::ApicId,
+        address: u32,
+        gsi_base: u32,
+    },
+    InterruptSourceOverride {
+        bus: u8,
+        source: u8,
+        gsi: u32,
+        flags: MpsIntiFlags,
+    },
+    NmiSource {
+        gsi: u32,
+        flags: MpsIntiFlags,
+    },
+    LocalApicNmi {
+        processor_uid: u8,
+        flags: MpsIntiFlags,
+        lint: u8,
+    },
+    LocalApicAddressOverride {
+        address: u64,
+    },
+    LocalX2Apic {
+        x2apic_id: X2ApicId,
+        flags: LocalApicFlags,
+        processor_uid: u32,
+    },
+    LocalX2ApicNmi {
+        processor_uid: u32,
+        flags: MpsIntiFlags,
+        lint: u8,
+    },
+    Unknown {
+        entry_type: u8,
+        data: &'a [u8],
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Madt<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Madt<'a> {
+    pub const ALL_PROCESSORS: u8 = 0xff;
+    pub const ALL_X2APIC_PROCESSORS: u32 = 0xffff_ffff;
+
+    pub fn parse(data: &'a [u8]) -> Result<Self, MadtError> {
+        if data.len() < ENTRIES_OFFSET {
+            return Err(MadtError::InvalidLength);
+        }
+
+        if &data[0..4] != b"APIC" {
+            return Err(MadtError::InvalidSignature);
+        }
+
+        let length = read_u32(data, 4) as usize;
+        if length < ENTRIES_OFFSET || length > data.len() {
+            return Err(MadtError::InvalidLength);
+        }
+
+        let data = &data[..length];
+        if data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(MadtError::InvalidChecksum);
+        }
+
+        Ok(Madt { data })
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.data[8]
+    }
+
+    pub fn oem_id(&self) -> &'a [u8] {
+        &self.data[10..16]
+    }
+
+    pub fn local_apic_address(&self) -> u32 {
+        read_u32(self.data, HEADER_LENGTH)
+    }
+
+    pub fn flags(&self) -> MadtFlags {
+        MadtFlags::from_bits_truncate(read_u32(self.data, HEADER_LENGTH + 4))
+    }
+
+    pub fn has_legacy_pics(&self) -> bool {
+        self.flags().contains(MadtFlags::PCAT_COMPAT)
+    }
+
+    pub fn effective_local_apic_address(&self) -> Result<u64, MadtError> {
+        for entry in self.entries() {
+            if let MadtEntry::LocalApicAddressOverride { address } = entry? {
+                return Ok(address);
+            }
+        }
+
+        Ok(self.local_apic_address() as u64)
+    }
+
+    pub fn entries(&self) -> MadtEntries<'a> {
+        MadtEntries {
+            data: self.data,
+            offset: ENTRIES_OFFSET,
+        }
+    }
+}
+
+pub struct MadtEntries<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MadtEntries<'a> {
+    fn parse_entry(offset: usize, entry_type: u8, data: &'a [u8]) -> Result<MadtEntry<'a>, MadtError> {
+        let expect = |length: usize| {
+            if data.len() < length {
+                Err(MadtError::TruncatedEntry { offset })
+            } else {
+                Ok(())
+            }
+        };
+
+        let entry = match entry_type {
+            0x0 => {
+                expect(8)?;
+                MadtEntry::LocalApic {
+                    processor_uid: data[2],
+                    apic_id: local::ApicId::Id8Bit(data[3] as u32),
+                    flags: LocalApicFlags::from_bits_truncate(read_u32(data, 4)),
+                }
+            }
+            0x1 => {
+                expect(12)?;
+                MadtEntry::IoApic {
+                    id: io::ApicId(data[2] as u32),
+                    address: read_u32(data, 4),
+                    gsi_base: read_u32(data, 8),
+                }
+            }
+            0x2 => {
+                expect(10)?;
+                MadtEntry::InterruptSourceOverride {
+                    bus: data[2],
+                    source: data[3],
+                    gsi: read_u32(data, 4),
+                    flags: inti_flags(read_u16(data, 8))?,
+                }
+            }
+            0x3 => {
+                expect(8)?;
+                MadtEntry::NmiSource {
+                    flags: inti_flags(read_u16(data, 2))?,
+                    gsi: read_u32(data, 4),
+                }
+            }
+            0x4 => {
+                expect(6)?;
+                MadtEntry::LocalApicNmi {
+                    processor_uid: data[2],
+                    flags: inti_flags(read_u16(data, 3))?,
+                    lint: data[5],
+                }
+            }
+            0x5 => {
+                expect(12)?;
+                MadtEntry::LocalApicAddressOverride {
+                    address: read_u64(data, 4),
+                }
+            }
+            0x9 => {
+                expect(16)?;
+                MadtEntry::LocalX2Apic {
+                    x2apic_id: X2ApicId(read_u32(data, 4)),
+                    flags: LocalApicFlags::from_bits_truncate(read_u32(data, 8)),
+                    processor_uid: read_u32(data, 12),
+                }
+            }
+            0xa => {
+                expect(12)?;
+                MadtEntry::LocalX2ApicNmi {
+                    flags: inti_flags(read_u16(data, 2))?,
+                    processor_uid: read_u32(data, 4),
+                    lint: data[8],
+                }
+            }
+            _ => MadtEntry::Unknown { entry_type, data },
+        };
+
+        Ok(entry)
+    }
+}
+
+impl<'a> Iterator for MadtEntries<'a> {
+    type Item = Result<MadtEntry<'a>, MadtError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+
+        let offset = self.offset;
+        let remaining = &self.data[offset..];
+        if remaining.len() < 2 || (remaining[1] as usize) < 2 || remaining[1] as usize > remaining.len() {
+            // stop iterating rather than loop on a corrupt entry
+            self.offset = self.data.len();
+            return Some(Err(MadtError::TruncatedEntry { offset }));
+        }
+
+        let length = remaining[1] as usize;
+        self.offset += length;
+
+        Some(Self::parse_entry(offset, remaining[0], &remaining[..length]))
+    }
+}
+
+fn inti_flags(value: u16) -> Result<MpsIntiFlags, MadtError> {
+    MpsIntiFlags::try_from(value)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // qemu q35 with two cpus
+    const QEMU_MADT: [u8; 128] = [
+        0x41, 0x50, 0x49, 0x43, 0x80, 0x00, 0x00, 0x00, 0x01, 0xda, 0x42, 0x4f, 0x43, 0x48, 0x53, 0x20,
+        0x42, 0x58, 0x50, 0x43, 0x41, 0x50, 0x49, 0x43, 0x01, 0x00, 0x00, 0x00, 0x42, 0x58, 0x50, 0x43,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0xfe, 0x01, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x08, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x00, 0x00,
+        0x00, 0x00, 0xc0, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x02, 0x0a, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x02, 0x0a, 0x00, 0x05, 0x05, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x02, 0x0a, 0x00, 0x09,
+        0x09, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x02, 0x0a, 0x00, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x00,
+        0x02, 0x0a, 0x00, 0x0b, 0x0b, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x04, 0x06, 0xff, 0x00, 0x00, 0x01,
+    ];
+
+    // x2apic processor, nmi source, address override and a second ioapic
+    const X2APIC_MADT: [u8; 104] = [
+        0x41, 0x50, 0x49, 0x43, 0x68, 0x00, 0x00, 0x00, 0x04, 0x51, 0x4f, 0x45, 0x4d, 0x49, 0x44, 0x20,
+        0x4f, 0x45, 0x4d, 0x54, 0x41, 0x42, 0x4c, 0x45, 0x01, 0x00, 0x00, 0x00, 0x43, 0x52, 0x54, 0x52,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x09, 0x10, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x0c, 0x05, 0x00,
+        0xff, 0xff, 0xff, 0xff, 0x01, 0x00, 0x00, 0x00, 0x03, 0x08, 0x0f, 0x00, 0x03, 0x00, 0x00, 0x00,
+        0x05, 0x0c, 0x00, 0x00, 0x00, 0x00, 0xe0, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0c, 0x02, 0x00,
+        0x00, 0x10, 0xc0, 0xfe, 0x18, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    pub fn test_qemu_madt() {
+        let madt = Madt::parse(&QEMU_MADT).expect("madt");
+        assert_eq!(madt.local_apic_address(), 0xfee0_0000);
+        assert!(madt.has_legacy_pics());
+        assert_eq!(madt.oem_id(), b"BOCHS ");
+
+        let entries: Vec<MadtEntry> = madt.entries().collect::<Result<_, _>>().expect("entries");
+        assert_eq!(entries.len(), 9);
+        assert_eq!(entries[1], MadtEntry::LocalApic {
+            processor_uid: 1,
+            apic_id: local::ApicId::Id8Bit(1),
+            flags: LocalApicFlags::ENABLED,
+        });
+        assert_eq!(entries[2], MadtEntry::IoApic { id: io::ApicId(0), address: 0xfec0_0000, gsi_base: 0 });
+        assert_eq!(entries[3], MadtEntry::InterruptSourceOverride {
+            bus: 0,
+            source: 0,
+            gsi: 2,
+            flags: MpsIntiFlags { polarity: None, trigger_mode: None },
+        });
+        assert_eq!(entries[4], MadtEntry::InterruptSourceOverride {
+            bus: 0,
+            source: 5,
+            gsi: 5,
+            flags: MpsIntiFlags { polarity: Some(Polarity::ActiveHigh), trigger_mode: Some(TriggerMode::Level) },
+        });
+        assert_eq!(entries[8], MadtEntry::LocalApicNmi {
+            processor_uid: Madt::ALL_PROCESSORS,
+            flags: MpsIntiFlags { polarity: None, trigger_mode: None },
+            lint: 1,
+        });
+    }
+
+    #[test]
+    pub fn test_x2apic_madt() {
+        let madt = Madt::parse(&X2APIC_MADT).expect("madt");
+        assert!(!madt.has_legacy_pics());
+        assert_eq!(madt.effective_local_apic_address(), Ok(0xfee0_0000));
+
+        let entries: Vec<MadtEntry> = madt.entries().collect::<Result<_, _>>().expect("entries");
+        assert_eq!(entries[0], MadtEntry::LocalX2Apic {
+            x2apic_id: X2ApicId(0x100),
+            flags: LocalApicFlags::ENABLED,
+            processor_uid: 0,
+        });
+        assert_eq!(entries[1], MadtEntry::LocalX2ApicNmi {
+            processor_uid: Madt::ALL_X2APIC_PROCESSORS,
+            flags: MpsIntiFlags { polarity: Some(Polarity::ActiveHigh), trigger_mode: Some(TriggerMode::Edge) },
+            lint: 1,
+        });
+        assert_eq!(entries[2], MadtEntry::NmiSource {
+            gsi: 3,
+            flags: MpsIntiFlags { polarity: Some(Polarity::ActiveLow), trigger_mode: Some(TriggerMode::Level) },
+        });
+        assert_eq!(entries[4], MadtEntry::IoApic { id: io::ApicId(2), address: 0xfec0_1000, gsi_base: 24 });
+    }
+
+    #[test]
+    pub fn test_invalid_tables() {
+        assert_eq!(Madt::parse(&QEMU_MADT[..40]).err(), Some(MadtError::InvalidLength));
+
+        let mut data = QEMU_MADT;
+        data[0] = b'X';
+        assert_eq!(Madt::parse(&data).err(), Some(MadtError::InvalidSignature));
+
+        let mut data = QEMU_MADT;
+        data[9] = 0;
+        assert_eq!(Madt::parse(&data).err(), Some(MadtError::InvalidChecksum));
+
+        // an entry claiming to run past the end of the table
+        let mut data = QEMU_MADT;
+        data[123] = 0x10;
+        data[9] = data[9].wrapping_sub(0x0a);
+        let madt = Madt::parse(&data).expect("madt");
+        assert_eq!(madt.entries().last(), Some(Err(MadtError::TruncatedEntry { offset: 122 })));
+
+        // an entry too short for its type
+        let mut data = QEMU_MADT;
+        data[123] = 0x05;
+        data[9] = data[9].wrapping_add(0x01);
+        let madt = Madt::parse(&data).expect("madt");
+        assert_eq!(madt.entries().nth(8), Some(Err(MadtError::TruncatedEntry { offset: 122 })));
+    }
+
+    #[test]
+    pub fn test_reserved_inti_flags() {
+        assert_eq!(MpsIntiFlags::try_from(0x2), Err(MadtError::ReservedIntiFlags(0x2)));
+        assert_eq!(MpsIntiFlags::try_from(0x8), Err(MadtError::ReservedIntiFlags(0x8)));
+    }
+}
@@ -0,0 +1,299 @@
+use core::convert::TryFrom;
+use core::result::Result;
+use crate::error::RegisterError;
+use crate::io::{DeliveryError, DeliveryMode, DestinationMode, TriggerMode, Vector, RedirectionEntry};
+
+bitflags! {
+    pub struct MsiAddressFlags: u32 {
+        const BASE                    = 0xfff0_0000;
+        const DESTINATION_ID          = 0x000f_f000;
+        const EXTENDED_DESTINATION_ID = 0x0000_0fe0;
+        const RESERVED                = 0x0000_0013;
+        const REDIRECTION_HINT        = 0x0000_0008;
+        const DESTINATION_MODE        = 0x0000_0004;
+    }
+}
+
+impl MsiAddressFlags {
+    pub const BASE_ADDRESS: u32 = 0xfee0_0000;
+
+    pub fn is_apic_window(&self) -> bool {
+        (*self & MsiAddressFlags::BASE).bits() == Self::BASE_ADDRESS
+    }
+
+    pub fn destination_id(&self) -> u32 {
+        (*self & MsiAddressFlags::DESTINATION_ID).bits() >> 12
+    }
+
+    pub fn extended_destination_id(&self) -> u32 {
+        let high = (*self & MsiAddressFlags::EXTENDED_DESTINATION_ID).bits() >> 5;
+        (high << 8) | self.destination_id()
+    }
+
+    pub fn redirection_hint(&self) -> bool {
+        self.contains(MsiAddressFlags::REDIRECTION_HINT)
+    }
+
+    pub fn destination_mode(&self) -> DestinationMode {
+        if self.contains(MsiAddressFlags::DESTINATION_MODE) {
+            DestinationMode::Logical
+        } else {
+            DestinationMode::Physical
+        }
+    }
+}
+
+bitflags! {
+    pub struct MsiDataFlags: u32 {
+        const VECTOR        = 0x0000_00ff;
+        const DELIVERY_MODE = 0x0000_0700;
+        const RESERVED      = 0xffff_3800;
+        const LEVEL         = 0x0000_4000;
+        const TRIGGER_MODE  = 0x0000_8000;
+    }
+}
+
+impl MsiDataFlags {
+    pub fn vector(&self) -> Vector {
+        Vector((*self & MsiDataFlags::VECTOR).bits())
+    }
+
+    pub fn delivery_mode(&self) -> Result<DeliveryMode, RegisterError> {
+        let value = (*self & MsiDataFlags::DELIVERY_MODE).bits() >> 8;
+        DeliveryMode::try_from(value as u8).map_err(|_| RegisterError::ReservedValue { field: "delivery mode", value })
+    }
+
+    pub fn trigger_mode(&self) -> TriggerMode {
+        if self.contains(MsiDataFlags::TRIGGER_MODE) {
+            TriggerMode::Level
+        } else {
+            TriggerMode::Edge
+        }
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.contains(MsiDataFlags::LEVEL)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MsiError {
+    NotApicWindow(u32),
+    DestinationOutOfRange(u32),
+    VectorOutOfRange(Vector),
+    ReservedDeliveryMode(DeliveryMode),
+    Register(RegisterError),
+}
+
+impl From<RegisterError> for MsiError {
+    fn from(error: RegisterError) -> Self {
+        MsiError::Register(error)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MsiMessage {
+    pub destination_id: u32,
+    pub destination_mode: DestinationMode,
+    pub redirection_hint: bool,
+    pub vector: Vector,
+    pub delivery_mode: DeliveryMode,
+    pub trigger_mode: TriggerMode,
+}
+
+impl MsiMessage {
+    pub const MAX_DESTINATION_ID: u32 = 0xff;
+    pub const MAX_EXTENDED_DESTINATION_ID: u32 = 0x7fff;
+
+    pub fn new(vector: Vector, destination_id: u32) -> Self {
+        MsiMessage {
+            destination_id,
+            destination_mode: DestinationMode::Physical,
+            redirection_hint: false,
+            vector,
+            delivery_mode: DeliveryMode::Fixed,
+            trigger_mode: TriggerMode::Edge,
+        }
+    }
+
+    pub fn with_destination_mode(mut self, destination_mode: DestinationMode) -> Self {
+        self.destination_mode = destination_mode;
+        self
+    }
+
+    pub fn with_redirection_hint(mut self, redirection_hint: bool) -> Self {
+        self.redirection_hint = redirection_hint;
+        self
+    }
+
+    pub fn with_delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
+        self.delivery_mode = delivery_mode;
+        self
+    }
+
+    pub fn with_trigger_mode(mut self, trigger_mode: TriggerMode) -> Self {
+        self.trigger_mode = trigger_mode;
+        self
+    }
+
+    pub fn address(&self) -> Result<MsiAddressFlags, MsiError> {
+        if self.destination_id > Self::MAX_DESTINATION_ID {
+            return Err(MsiError::DestinationOutOfRange(self.destination_id));
+        }
+
+        self.extended_address()
+    }
+
+    pub fn extended_address(&self) -> Result<MsiAddressFlags, MsiError> {
+        if self.destination_id > Self::MAX_EXTENDED_DESTINATION_ID {
+            return Err(MsiError::DestinationOutOfRange(self.destination_id));
+        }
+
+        let mut address = MsiAddressFlags::from_bits_truncate(
+            MsiAddressFlags::BASE_ADDRESS
+                | ((self.destination_id & 0xff) << 12)
+                | ((self.destination_id >> 8) << 5));
+
+        if self.redirection_hint {
+            address |= MsiAddressFlags::REDIRECTION_HINT;
+        }
+        if self.destination_mode == DestinationMode::Logical {
+            address |= MsiAddressFlags::DESTINATION_MODE;
+        }
+
+        Ok(address)
+    }
+
+    pub fn data(&self) -> Result<MsiDataFlags, MsiError> {
+        RedirectionEntry::check_delivery(self.vector, self.delivery_mode).map_err(|error| match error {
+            DeliveryError::ReservedDeliveryMode => MsiError::ReservedDeliveryMode(self.delivery_mode),
+            DeliveryError::VectorOutOfRange => MsiError::VectorOutOfRange(self.vector),
+        })?;
+
+        let mut data = MsiDataFlags::from_bits_truncate((self.vector.0 & 0xff) | (self.delivery_mode.as_u8() as u32) << 8);
+        if self.trigger_mode == TriggerMode::Level {
+            data |= MsiDataFlags::TRIGGER_MODE | MsiDataFlags::LEVEL;
+        }
+
+        Ok(data)
+    }
+
+    pub fn decode(address: MsiAddressFlags, data: MsiDataFlags) -> Result<Self, MsiError> {
+        if !address.is_apic_window() {
+            return Err(MsiError::NotApicWindow(address.bits()));
+        }
+
+        Ok(MsiMessage {
+            destination_id: address.extended_destination_id(),
+            destination_mode: address.destination_mode(),
+            redirection_hint: address.redirection_hint(),
+            vector: data.vector(),
+            delivery_mode: data.delivery_mode()?,
+            trigger_mode: data.trigger_mode(),
+        })
+    }
+}
+
+bitflags! {
+    pub struct MsiXVectorControlFlags: u32 {
+        const MASK     = 0x0000_0001;
+        const RESERVED = 0xffff_fffe;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct MsiXTableEntry {
+    pub address_low: u32,
+    pub address_high: u32,
+    pub data: u32,
+    pub vector_control: u32,
+}
+
+impl MsiXTableEntry {
+    pub fn new(address: MsiAddressFlags, data: MsiDataFlags, masked: bool) -> Self {
+        let vector_control = if masked { MsiXVectorControlFlags::MASK } else { MsiXVectorControlFlags::empty() };
+
+        MsiXTableEntry {
+            address_low: address.bits(),
+            address_high: 0,
+            data: data.bits(),
+            vector_control: vector_control.bits(),
+        }
+    }
+
+    pub fn address(&self) -> MsiAddressFlags {
+        MsiAddressFlags::from_bits_truncate(self.address_low)
+    }
+
+    pub fn data(&self) -> MsiDataFlags {
+        MsiDataFlags::from_bits_truncate(self.data)
+    }
+
+    pub fn is_masked(&self) -> bool {
+        MsiXVectorControlFlags::from_bits_truncate(self.vector_control).contains(MsiXVectorControlFlags::MASK)
+    }
+
+    pub fn message(&self) -> Result<MsiMessage, MsiError> {
+        MsiMessage::decode(self.address(), self.data())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_encode_physical() {
+        let message = MsiMessage::new(Vector(0x41), 3);
+
+        assert_eq!(message.address().map(|address| address.bits()), Ok(0xfee0_3000));
+        assert_eq!(message.data().map(|data| data.bits()), Ok(0x0000_0041));
+    }
+
+    #[test]
+    pub fn test_encode_logical_level() {
+        let message = MsiMessage::new(Vector(0x60), 0x0f)
+            .with_destination_mode(DestinationMode::Logical)
+            .with_redirection_hint(true)
+            .with_delivery_mode(DeliveryMode::LowestPriority)
+            .with_trigger_mode(TriggerMode::Level);
+
+        let address = message.address().expect("address");
+        let data = message.data().expect("data");
+        assert_eq!(address.bits(), 0xfee0_f00c);
+        assert_eq!(data.bits(), 0x0000_c160);
+        assert_eq!(MsiMessage::decode(address, data), Ok(message));
+    }
+
+    #[test]
+    pub fn test_extended_destination() {
+        let message = MsiMessage::new(Vector(0x41), 0x1234);
+
+        assert_eq!(message.address(), Err(MsiError::DestinationOutOfRange(0x1234)));
+        let address = message.extended_address().expect("address");
+        assert_eq!(address.bits(), 0xfee3_4240);
+        assert_eq!(address.destination_id(), 0x34);
+        assert_eq!(address.extended_destination_id(), 0x1234);
+        assert_eq!(MsiMessage::new(Vector(0x41), 0x8000).extended_address(), Err(MsiError::DestinationOutOfRange(0x8000)));
+    }
+
+    #[test]
+    pub fn test_validation() {
+        assert_eq!(MsiMessage::new(Vector(0x0f), 0).data(), Err(MsiError::VectorOutOfRange(Vector(0x0f))));
+        assert!(MsiMessage::new(Vector(0), 0).with_delivery_mode(DeliveryMode::NMI).data().is_ok());
+        assert_eq!(MsiMessage::new(Vector(0x41), 0).with_delivery_mode(DeliveryMode::Reserved0).data(),
+            Err(MsiError::ReservedDeliveryMode(DeliveryMode::Reserved0)));
+        assert_eq!(MsiMessage::decode(MsiAddressFlags::from_bits_truncate(0xfec0_0000), MsiDataFlags::empty()),
+            Err(MsiError::NotApicWindow(0xfec0_0000)));
+    }
+
+    #[test]
+    pub fn test_msix_entry() {
+        let message = MsiMessage::new(Vector(0x41), 3);
+        let entry = MsiXTableEntry::new(message.address().unwrap(), message.data().unwrap(), true);
+
+        assert!(entry.is_masked());
+        assert_eq!(entry.message(), Ok(message));
+    }
+}
@@ -0,0 +1,46 @@
+pub trait Msr {
+    unsafe fn read_msr(&self, msr: u32) -> u64;
+    unsafe fn write_msr(&self, msr: u32, value: u64);
+}
+
+/// Msr double for unit tests. Unwritten msrs read as zero and every write is recorded in order.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct FakeMsr {
+    values: std::cell::RefCell<std::collections::HashMap<u32, u64>>,
+    writes: std::cell::RefCell<std::vec::Vec<(u32, u64)>>,
+}
+
+#[cfg(test)]
+impl FakeMsr {
+    pub fn with(msr: u32, value: u64) -> Self {
+        let fake = FakeMsr::default();
+        fake.set(msr, value);
+        fake
+    }
+
+    /// Changes an msr behind the caller's back, without recording a write.
+    pub fn set(&self, msr: u32, value: u64) {
+        self.values.borrow_mut().insert(msr, value);
+    }
+
+    pub fn value(&self, msr: u32) -> u64 {
+        *self.values.borrow().get(&msr).unwrap_or(&0)
+    }
+
+    pub fn writes(&self) -> std::vec::Vec<(u32, u64)> {
+        self.writes.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl Msr for FakeMsr {
+    unsafe fn read_msr(&self, msr: u32) -> u64 {
+        self.value(msr)
+    }
+
+    unsafe fn write_msr(&self, msr: u32, value: u64) {
+        self.set(msr, value);
+        self.writes.borrow_mut().push((msr, value));
+    }
+}
@@ -0,0 +1,430 @@
+use core::result::Result;
+use crate::local::InterruptVector;
+
+pub trait PortIo {
+    unsafe fn read_port_8(&self, port: u16) -> u8;
+    unsafe fn write_port_8(&self, port: u16, value: u8);
+}
+
+pub const POST_PORT: u16 = 0x80;
+pub const SPURIOUS_IRQ: u8 = 7;
+pub const CASCADE_IRQ: u8 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pic {
+    Master,
+    Slave,
+}
+
+impl Pic {
+    pub fn command_port(self) -> u16 {
+        match self {
+            Pic::Master => 0x20,
+            Pic::Slave => 0xa0,
+        }
+    }
+
+    pub fn data_port(self) -> u16 {
+        self.command_port() + 1
+    }
+
+    pub fn for_irq(irq: u8) -> Result<(Self, u8), PicError> {
+        match irq {
+            0..=7 => Ok((Pic::Master, irq)),
+            8..=15 => Ok((Pic::Slave, irq - 8)),
+            _ => Err(PicError::InvalidIrq(irq)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PicError {
+    InvalidOffset(u8),
+    InvalidIrq(u8),
+}
+
+bitflags! {
+    pub struct Icw1Flags: u8 {
+        const ICW4_NEEDED     = 0b0000_0001;
+        const SINGLE          = 0b0000_0010;
+        const INTERVAL_4      = 0b0000_0100;
+        const LEVEL_TRIGGERED = 0b0000_1000;
+        const INIT            = 0b0001_0000;
+        const RESERVED        = 0b1110_0000;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Icw2(pub u8);
+
+impl Icw2 {
+    pub fn new(offset: u8) -> Result<Self, PicError> {
+        if offset & 0x7 != 0 || offset < 0x20 {
+            Err(PicError::InvalidOffset(offset))
+        } else {
+            Ok(Icw2(offset))
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Icw3(pub u8);
+
+impl Icw3 {
+    pub fn master(cascade_irq: u8) -> Self {
+        Icw3(1 << (cascade_irq & 0x7))
+    }
+
+    pub fn slave(cascade_irq: u8) -> Self {
+        Icw3(cascade_irq & 0x7)
+    }
+}
+
+bitflags! {
+    pub struct Icw4Flags: u8 {
+        const MODE_8086            = 0b0000_0001;
+        const AUTO_EOI             = 0b0000_0010;
+        const BUFFERED_MASTER      = 0b0000_0100;
+        const BUFFERED             = 0b0000_1000;
+        const SPECIAL_FULLY_NESTED = 0b0001_0000;
+        const RESERVED             = 0b1110_0000;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ocw1(pub u8);
+
+bitflags! {
+    pub struct Ocw2Flags: u8 {
+        const LEVEL    = 0b0000_0111;
+        const SELECT   = 0b0001_1000;
+        const EOI      = 0b0010_0000;
+        const SPECIFIC = 0b0100_0000;
+        const ROTATE   = 0b1000_0000;
+    }
+}
+
+impl Ocw2Flags {
+    pub fn non_specific_eoi() -> Self {
+        Ocw2Flags::EOI
+    }
+
+    pub fn specific_eoi(level: u8) -> Self {
+        Ocw2Flags::EOI | Ocw2Flags::SPECIFIC | Ocw2Flags::from_bits_truncate(level & 0x7)
+    }
+
+    pub fn level(&self) -> u8 {
+        (*self & Ocw2Flags::LEVEL).bits()
+    }
+}
+
+bitflags! {
+    pub struct Ocw3Flags: u8 {
+        const READ_IN_SERVICE      = 0b0000_0001;
+        const READ_REGISTER        = 0b0000_0010;
+        const POLL                 = 0b0000_0100;
+        const SELECT               = 0b0000_1000;
+        const SPECIAL_MASK         = 0b0010_0000;
+        const SPECIAL_MASK_COMMAND = 0b0100_0000;
+    }
+}
+
+impl Ocw3Flags {
+    pub fn read_interrupt_request() -> Self {
+        Ocw3Flags::SELECT | Ocw3Flags::READ_REGISTER
+    }
+
+    pub fn read_in_service() -> Self {
+        Ocw3Flags::SELECT | Ocw3Flags::READ_REGISTER | Ocw3Flags::READ_IN_SERVICE
+    }
+}
+
+pub struct ChainedPics<'a> {
+    io: &'a dyn PortIo,
+    master_offset: Icw2,
+    slave_offset: Icw2,
+}
+
+impl<'a> ChainedPics<'a> {
+    pub fn new(io: &'a dyn PortIo, master_offset: u8, slave_offset: u8) -> Result<Self, PicError> {
+        let master_offset = Icw2::new(master_offset)?;
+        let slave_offset = Icw2::new(slave_offset)?;
+        if master_offset == slave_offset {
+            return Err(PicError::InvalidOffset(slave_offset.0));
+        }
+
+        Ok(ChainedPics { io, master_offset, slave_offset })
+    }
+
+    pub fn vector(&self, irq: u8) -> Result<InterruptVector, PicError> {
+        match Pic::for_irq(irq)? {
+            (Pic::Master, line) => Ok(InterruptVector((self.master_offset.0 + line) as u32)),
+            (Pic::Slave, line) => Ok(InterruptVector((self.slave_offset.0 + line) as u32)),
+        }
+    }
+
+    pub fn irq(&self, vector: InterruptVector) -> Option<u8> {
+        let master = self.master_offset.0 as u32;
+        let slave = self.slave_offset.0 as u32;
+
+        match vector.0 {
+            v if v >= master && v < master + 8 => Some((v - master) as u8),
+            v if v >= slave && v < slave + 8 => Some((v - slave) as u8 + 8),
+            _ => None,
+        }
+    }
+
+    pub unsafe fn remap(&self) {
+        let masks = self.read_masks();
+        let icw1 = Icw1Flags::INIT | Icw1Flags::ICW4_NEEDED;
+
+        self.write(Pic::Master.command_port(), icw1.bits());
+        self.write(Pic::Slave.command_port(), icw1.bits());
+        self.write(Pic::Master.data_port(), self.master_offset.0);
+        self.write(Pic::Slave.data_port(), self.slave_offset.0);
+        self.write(Pic::Master.data_port(), Icw3::master(CASCADE_IRQ).0);
+        self.write(Pic::Slave.data_port(), Icw3::slave(CASCADE_IRQ).0);
+        self.write(Pic::Master.data_port(), Icw4Flags::MODE_8086.bits());
+        self.write(Pic::Slave.data_port(), Icw4Flags::MODE_8086.bits());
+
+        self.write_masks(masks);
+    }
+
+    pub unsafe fn mask_all(&self) {
+        self.write_masks(0xffff);
+    }
+
+    pub unsafe fn disable(&self) {
+        self.remap();
+        self.mask_all();
+    }
+
+    pub unsafe fn read_masks(&self) -> u16 {
+        let master = self.io.read_port_8(Pic::Master.data_port()) as u16;
+        let slave = self.io.read_port_8(Pic::Slave.data_port()) as u16;
+
+        (slave << 8) | master
+    }
+
+    pub unsafe fn write_masks(&self, masks: u16) {
+        self.io.write_port_8(Pic::Master.data_port(), Ocw1(masks as u8).0);
+        self.io.write_port_8(Pic::Slave.data_port(), Ocw1((masks >> 8) as u8).0);
+    }
+
+    pub unsafe fn mask(&self, irq: u8) -> Result<(), PicError> {
+        Pic::for_irq(irq)?;
+        self.write_masks(self.read_masks() | 1 << irq);
+        Ok(())
+    }
+
+    pub unsafe fn unmask(&self, irq: u8) -> Result<(), PicError> {
+        Pic::for_irq(irq)?;
+        self.write_masks(self.read_masks() & !(1 << irq));
+        Ok(())
+    }
+
+    pub unsafe fn read_interrupt_request(&self) -> u16 {
+        self.read_register(Ocw3Flags::read_interrupt_request())
+    }
+
+    pub unsafe fn read_in_service(&self) -> u16 {
+        self.read_register(Ocw3Flags::read_in_service())
+    }
+
+    pub unsafe fn end_of_interrupt(&self, irq: u8) -> Result<(), PicError> {
+        if let (Pic::Slave, _) = Pic::for_irq(irq)? {
+            self.io.write_port_8(Pic::Slave.command_port(), Ocw2Flags::non_specific_eoi().bits());
+        }
+
+        self.io.write_port_8(Pic::Master.command_port(), Ocw2Flags::non_specific_eoi().bits());
+        Ok(())
+    }
+
+    pub unsafe fn acknowledge_spurious(&self, irq: u8) -> Result<bool, PicError> {
+        let (pic, line) = Pic::for_irq(irq)?;
+        if line != SPURIOUS_IRQ {
+            return Ok(false);
+        }
+
+        let in_service = self.read_in_service();
+        let spurious = match pic {
+            Pic::Master => in_service & 0x0080 == 0,
+            Pic::Slave => in_service & 0x8000 == 0,
+        };
+
+        // the master saw a real request on the cascade line and still needs its eoi
+        if spurious && pic == Pic::Slave {
+            self.io.write_port_8(Pic::Master.command_port(), Ocw2Flags::non_specific_eoi().bits());
+        }
+
+        Ok(spurious)
+    }
+
+    unsafe fn read_register(&self, command: Ocw3Flags) -> u16 {
+        self.io.write_port_8(Pic::Master.command_port(), command.bits());
+        self.io.write_port_8(Pic::Slave.command_port(), command.bits());
+
+        let master = self.io.read_port_8(Pic::Master.command_port()) as u16;
+        let slave = self.io.read_port_8(Pic::Slave.command_port()) as u16;
+
+        (slave << 8) | master
+    }
+
+    unsafe fn write(&self, port: u16, value: u8) {
+        self.io.write_port_8(port, value);
+        self.io.write_port_8(POST_PORT, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct FakePic {
+        mask: u8,
+        interrupt_request: u8,
+        in_service: u8,
+        read_in_service: bool,
+        init_step: usize,
+        offset: u8,
+        eois: usize,
+    }
+
+    #[derive(Default)]
+    struct FakePorts {
+        pics: RefCell<[FakePic; 2]>,
+        writes: RefCell<Vec<(u16, u8)>>,
+    }
+
+    impl FakePorts {
+        fn pic(port: u16) -> usize {
+            if port & 0xf0 == 0xa0 { 1 } else { 0 }
+        }
+    }
+
+    impl PortIo for FakePorts {
+        unsafe fn read_port_8(&self, port: u16) -> u8 {
+            let pics = self.pics.borrow();
+            let pic = &pics[Self::pic(port)];
+
+            match port & 0x1 {
+                0 if pic.read_in_service => pic.in_service,
+                0 => pic.interrupt_request,
+                _ => pic.mask,
+            }
+        }
+
+        unsafe fn write_port_8(&self, port: u16, value: u8) {
+            self.writes.borrow_mut().push((port, value));
+            if port == POST_PORT {
+                return;
+            }
+
+            let mut pics = self.pics.borrow_mut();
+            let pic = &mut pics[Self::pic(port)];
+
+            match port & 0x1 {
+                0 if value & Icw1Flags::INIT.bits() != 0 => pic.init_step = 1,
+                0 if value & Ocw3Flags::SELECT.bits() != 0 => {
+                    pic.read_in_service = value & Ocw3Flags::READ_IN_SERVICE.bits() != 0;
+                }
+                0 if value & Ocw2Flags::EOI.bits() != 0 => pic.eois += 1,
+                0 => {}
+                _ => match pic.init_step {
+                    1 => {
+                        pic.offset = value;
+                        pic.init_step = 2;
+                    }
+                    2 => pic.init_step = 3,
+                    3 => pic.init_step = 0,
+                    _ => pic.mask = value,
+                },
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_remap_preserves_masks() {
+        let ports = FakePorts::default();
+        let pics = ChainedPics::new(&ports, 0x20, 0x28).unwrap();
+
+        unsafe {
+            pics.write_masks(0xb8fb);
+            pics.remap();
+
+            assert_eq!(pics.read_masks(), 0xb8fb);
+            assert_eq!(ports.pics.borrow()[0].offset, 0x20);
+            assert_eq!(ports.pics.borrow()[1].offset, 0x28);
+            assert!(ports.writes.borrow().contains(&(0x21, 0x04)));
+            assert!(ports.writes.borrow().contains(&(0xa1, 0x02)));
+
+            pics.disable();
+            assert_eq!(pics.read_masks(), 0xffff);
+        }
+    }
+
+    #[test]
+    pub fn test_vectors() {
+        let ports = FakePorts::default();
+        let pics = ChainedPics::new(&ports, 0x20, 0x28).unwrap();
+
+        assert_eq!(pics.vector(3), Ok(InterruptVector(0x23)));
+        assert_eq!(pics.vector(15), Ok(InterruptVector(0x2f)));
+        assert_eq!(pics.vector(16), Err(PicError::InvalidIrq(16)));
+        assert_eq!(pics.irq(InterruptVector(0x2c)), Some(12));
+        assert_eq!(pics.irq(InterruptVector(0x30)), None);
+        assert_eq!(ChainedPics::new(&ports, 0x08, 0x28).err(), Some(PicError::InvalidOffset(0x08)));
+        assert_eq!(ChainedPics::new(&ports, 0x21, 0x28).err(), Some(PicError::InvalidOffset(0x21)));
+        assert_eq!(ChainedPics::new(&ports, 0x28, 0x28).err(), Some(PicError::InvalidOffset(0x28)));
+    }
+
+    #[test]
+    pub fn test_registers_and_eoi() {
+        let ports = FakePorts::default();
+        let pics = ChainedPics::new(&ports, 0x20, 0x28).unwrap();
+
+        unsafe {
+            {
+                let mut state = ports.pics.borrow_mut();
+                state[0].interrupt_request = 0x11;
+                state[1].in_service = 0x10;
+            }
+
+            assert_eq!(pics.read_interrupt_request(), 0x0011);
+            assert_eq!(pics.read_in_service(), 0x1000);
+
+            pics.end_of_interrupt(12).unwrap();
+            pics.end_of_interrupt(1).unwrap();
+            assert_eq!(ports.pics.borrow()[0].eois, 2);
+            assert_eq!(ports.pics.borrow()[1].eois, 1);
+
+            pics.mask(12).unwrap();
+            pics.unmask(12).unwrap();
+            assert_eq!(pics.mask(16), Err(PicError::InvalidIrq(16)));
+        }
+    }
+
+    #[test]
+    pub fn test_spurious() {
+        let ports = FakePorts::default();
+        let pics = ChainedPics::new(&ports, 0x20, 0x28).unwrap();
+
+        unsafe {
+            assert_eq!(pics.acknowledge_spurious(3), Ok(false));
+            assert_eq!(pics.acknowledge_spurious(7), Ok(true));
+            assert_eq!(ports.pics.borrow()[0].eois, 0);
+
+            assert_eq!(pics.acknowledge_spurious(15), Ok(true));
+            assert_eq!(ports.pics.borrow()[0].eois, 1);
+            assert_eq!(ports.pics.borrow()[1].eois, 0);
+
+            ports.pics.borrow_mut()[1].in_service = 0x80;
+            assert_eq!(pics.acknowledge_spurious(15), Ok(false));
+            ports.pics.borrow_mut()[0].in_service = 0x80;
+            assert_eq!(pics.acknowledge_spurious(7), Ok(false));
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use core::result::Result;
+use core::convert::TryFrom;
+use crate::io::{DeliveryMode, Destination, DestinationMode, RedirectionEntry, Vector};
+use crate::remapping::{RemappingError, validate_delivery};
+
+bitflags! {
+    pub struct AmdIrte32Flags: u32 {
+        const REMAP_ENABLE           = 0x0000_0001;
+        const SUPPRESS_IO_PAGE_FAULT = 0x0000_0002;
+        const INTERRUPT_TYPE         = 0x0000_001c;
+        const REQUEST_EOI            = 0x0000_0020;
+        const DESTINATION_MODE       = 0x0000_0040;
+        const RESERVED               = 0xff00_0080;
+        const DESTINATION            = 0x0000_ff00;
+        const VECTOR                 = 0x00ff_0000;
+    }
+}
+
+impl AmdIrte32Flags {
+    pub fn is_enabled(&self) -> bool {
+        self.contains(AmdIrte32Flags::REMAP_ENABLE)
+    }
+
+    pub fn delivery_mode(&self) -> DeliveryMode {
+        DeliveryMode::try_from(((*self & AmdIrte32Flags::INTERRUPT_TYPE).bits() >> 2) as u8).unwrap()
+    }
+
+    pub fn destination_mode(&self) -> DestinationMode {
+        if self.contains(AmdIrte32Flags::DESTINATION_MODE) {
+            DestinationMode::Logical
+        } else {
+            DestinationMode::Physical
+        }
+    }
+
+    pub fn destination(&self) -> u32 {
+        (*self & AmdIrte32Flags::DESTINATION).bits() >> 8
+    }
+
+    pub fn vector(&self) -> Vector {
+        Vector((*self & AmdIrte32Flags::VECTOR).bits() >> 16)
+    }
+}
+
+bitflags! {
+    pub struct AmdIrte128Flags: u128 {
+        const REMAP_ENABLE           = 0x0000_0000_0000_0000_0000_0000_0000_0001;
+        const SUPPRESS_IO_PAGE_FAULT = 0x0000_0000_0000_0000_0000_0000_0000_0002;
+        const INTERRUPT_TYPE         = 0x0000_0000_0000_0000_0000_0000_0000_001c;
+        const GA_LOG_INTERRUPT       = 0x0000_0000_0000_0000_0000_0000_0000_0004;
+        const REQUEST_EOI            = 0x0000_0000_0000_0000_0000_0000_0000_0020;
+        const DESTINATION_MODE       = 0x0000_0000_0000_0000_0000_0000_0000_0040;
+        const IS_RUNNING             = 0x0000_0000_0000_0000_0000_0000_0000_0040;
+        const GUEST_MODE             = 0x0000_0000_0000_0000_0000_0000_0000_0080;
+        const DESTINATION_LOW        = 0x0000_0000_0000_0000_0000_0000_ffff_ff00;
+        const GA_TAG                 = 0x0000_0000_0000_0000_ffff_ffff_0000_0000;
+        const VECTOR                 = 0x0000_0000_0000_00ff_0000_0000_0000_0000;
+        const RESERVED               = 0x00f0_0000_0000_0f00_0000_0000_0000_0000;
+        const GA_ROOT_POINTER        = 0x000f_ffff_ffff_f000_0000_0000_0000_0000;
+        const DESTINATION_HIGH       = 0xff00_0000_0000_0000_0000_0000_0000_0000;
+    }
+}
+
+impl AmdIrte128Flags {
+    pub fn from_halves(low: u64, high: u64) -> Self {
+        AmdIrte128Flags::from_bits_truncate(((high as u128) << 64) | low as u128)
+    }
+
+    pub fn low(&self) -> u64 {
+        self.bits() as u64
+    }
+
+    pub fn high(&self) -> u64 {
+        (self.bits() >> 64) as u64
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.contains(AmdIrte128Flags::REMAP_ENABLE)
+    }
+
+    pub fn is_guest_mode(&self) -> bool {
+        self.contains(AmdIrte128Flags::GUEST_MODE)
+    }
+
+    pub fn delivery_mode(&self) -> DeliveryMode {
+        DeliveryMode::try_from(((*self & AmdIrte128Flags::INTERRUPT_TYPE).bits() >> 2) as u8).unwrap()
+    }
+
+    pub fn destination_mode(&self) -> DestinationMode {
+        if self.contains(AmdIrte128Flags::DESTINATION_MODE) {
+            DestinationMode::Logical
+        } else {
+            DestinationMode::Physical
+        }
+    }
+
+    pub fn destination(&self) -> u32 {
+        let low = (*self & AmdIrte128Flags::DESTINATION_LOW).bits() >> 8;
+        let high = (*self & AmdIrte128Flags::DESTINATION_HIGH).bits() >> 120;
+        ((high << 24) | low) as u32
+    }
+
+    pub fn vector(&self) -> Vector {
+        Vector(((*self & AmdIrte128Flags::VECTOR).bits() >> 64) as u32)
+    }
+
+    pub fn ga_tag(&self) -> u32 {
+        ((*self & AmdIrte128Flags::GA_TAG).bits() >> 32) as u32
+    }
+
+    pub fn ga_root_pointer(&self) -> u64 {
+        ((*self & AmdIrte128Flags::GA_ROOT_POINTER).bits() >> 64) as u64
+    }
+
+    fn from_destination(destination: u32) -> Self {
+        let destination = destination as u128;
+        AmdIrte128Flags::from_bits_truncate(((destination & 0xff_ffff) << 8) | ((destination >> 24) << 120))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AmdIrte {
+    pub vector: Vector,
+    pub delivery_mode: DeliveryMode,
+    pub destination_mode: DestinationMode,
+    pub destination: u32,
+    pub request_eoi: bool,
+    pub suppress_io_page_fault: bool,
+}
+
+impl AmdIrte {
+    pub const MAX_32BIT_DESTINATION: u32 = 0xff;
+
+    pub fn new(vector: Vector, destination: u32) -> Self {
+        AmdIrte {
+            vector,
+            delivery_mode: DeliveryMode::Fixed,
+            destination_mode: DestinationMode::Physical,
+            destination,
+            request_eoi: false,
+            suppress_io_page_fault: false,
+        }
+    }
+
+    pub fn with_delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
+        self.delivery_mode = delivery_mode;
+        self
+    }
+
+    pub fn with_destination_mode(mut self, destination_mode: DestinationMode) -> Self {
+        self.destination_mode = destination_mode;
+        self
+    }
+
+    pub fn with_request_eoi(mut self, request_eoi: bool) -> Self {
+        self.request_eoi = request_eoi;
+        self
+    }
+
+    pub fn with_suppress_io_page_fault(mut self, suppress_io_page_fault: bool) -> Self {
+        self.suppress_io_page_fault = suppress_io_page_fault;
+        self
+    }
+
+    pub fn to_32bit_flags(&self) -> Result<AmdIrte32Flags, RemappingError> {
+        validate_delivery(self.vector, self.delivery_mode)?;
+
+        if self.destination > Self::MAX_32BIT_DESTINATION {
+            return Err(RemappingError::DestinationOutOfRange(self.destination));
+        }
+
+        let mut flags = AmdIrte32Flags::from_bits_truncate(
+            (self.delivery_mode.as_u8() as u32) << 2 | self.destination << 8 | (self.vector.0 & 0xff) << 16)
+            | AmdIrte32Flags::REMAP_ENABLE;
+
+        if self.suppress_io_page_fault {
+            flags |= AmdIrte32Flags::SUPPRESS_IO_PAGE_FAULT;
+        }
+        if self.request_eoi {
+            flags |= AmdIrte32Flags::REQUEST_EOI;
+        }
+        if self.destination_mode == DestinationMode::Logical {
+            flags |= AmdIrte32Flags::DESTINATION_MODE;
+        }
+
+        Ok(flags)
+    }
+
+    pub fn to_128bit_flags(&self) -> Result<AmdIrte128Flags, RemappingError> {
+        validate_delivery(self.vector, self.delivery_mode)?;
+
+        let mut flags = AmdIrte128Flags::from_bits_truncate(
+            (self.delivery_mode.as_u8() as u128) << 2 | (self.vector.0 as u128 & 0xff) << 64)
+            | AmdIrte128Flags::from_destination(self.destination)
+            | AmdIrte128Flags::REMAP_ENABLE;
+
+        if self.suppress_io_page_fault {
+            flags |= AmdIrte128Flags::SUPPRESS_IO_PAGE_FAULT;
+        }
+        if self.request_eoi {
+            flags |= AmdIrte128Flags::REQUEST_EOI;
+        }
+        if self.destination_mode == DestinationMode::Logical {
+            flags |= AmdIrte128Flags::DESTINATION_MODE;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl From<AmdIrte32Flags> for AmdIrte {
+    fn from(flags: AmdIrte32Flags) -> Self {
+        AmdIrte {
+            vector: flags.vector(),
+            delivery_mode: flags.delivery_mode(),
+            destination_mode: flags.destination_mode(),
+            destination: flags.destination(),
+            request_eoi: flags.contains(AmdIrte32Flags::REQUEST_EOI),
+            suppress_io_page_fault: flags.contains(AmdIrte32Flags::SUPPRESS_IO_PAGE_FAULT),
+        }
+    }
+}
+
+impl TryFrom<AmdIrte128Flags> for AmdIrte {
+    type Error = RemappingError;
+
+    fn try_from(flags: AmdIrte128Flags) -> Result<Self, Self::Error> {
+        if flags.is_guest_mode() {
+            return Err(RemappingError::GuestMode);
+        }
+
+        Ok(AmdIrte {
+            vector: flags.vector(),
+            delivery_mode: flags.delivery_mode(),
+            destination_mode: flags.destination_mode(),
+            destination: flags.destination(),
+            request_eoi: flags.contains(AmdIrte128Flags::REQUEST_EOI),
+            suppress_io_page_fault: flags.contains(AmdIrte128Flags::SUPPRESS_IO_PAGE_FAULT),
+        })
+    }
+}
+
+impl TryFrom<RedirectionEntry> for AmdIrte {
+    type Error = RemappingError;
+
+    fn try_from(entry: RedirectionEntry) -> Result<Self, Self::Error> {
+        let destination = match entry.destination {
+            Destination::Physical(id) | Destination::Logical(id) => id as u32,
+        };
+
+        let irte = AmdIrte::new(entry.vector, destination)
+            .with_delivery_mode(entry.delivery_mode)
+            .with_destination_mode(entry.destination_mode());
+
+        validate_delivery(irte.vector, irte.delivery_mode)?;
+        Ok(irte)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AmdGuestIrte {
+    pub vector: Vector,
+    pub ga_tag: u32,
+    pub ga_root_pointer: u64,
+    pub destination: u32,
+    pub is_running: bool,
+    pub ga_log_interrupt: bool,
+    pub suppress_io_page_fault: bool,
+}
+
+impl AmdGuestIrte {
+    pub const GA_ROOT_POINTER_ALIGNMENT: u64 = 0x1000;
+
+    pub fn new(vector: Vector, ga_tag: u32, ga_root_pointer: u64) -> Self {
+        AmdGuestIrte {
+            vector,
+            ga_tag,
+            ga_root_pointer,
+            destination: 0,
+            is_running: false,
+            ga_log_interrupt: false,
+            suppress_io_page_fault: false,
+        }
+    }
+
+    pub fn with_running(mut self, destination: u32) -> Self {
+        self.is_running = true;
+        self.destination = destination;
+        self
+    }
+
+    pub fn with_ga_log_interrupt(mut self, ga_log_interrupt: bool) -> Self {
+        self.ga_log_interrupt = ga_log_interrupt;
+        self
+    }
+
+    pub fn with_suppress_io_page_fault(mut self, suppress_io_page_fault: bool) -> Self {
+        self.suppress_io_page_fault = suppress_io_page_fault;
+        self
+    }
+
+    pub fn to_flags(&self) -> Result<AmdIrte128Flags, RemappingError> {
+        validate_delivery(self.vector, DeliveryMode::Fixed)?;
+
+        if self.ga_root_pointer & (Self::GA_ROOT_POINTER_ALIGNMENT - 1) != 0 {
+            return Err(RemappingError::MisalignedDescriptor(self.ga_root_pointer));
+        }
+
+        let mut flags = AmdIrte128Flags::from_bits_truncate(
+            (self.ga_tag as u128) << 32
+                | (self.vector.0 as u128 & 0xff) << 64
+                | (self.ga_root_pointer as u128) << 64)
+            | AmdIrte128Flags::from_destination(self.destination)
+            | AmdIrte128Flags::REMAP_ENABLE
+            | AmdIrte128Flags::GUEST_MODE;
+
+        if self.suppress_io_page_fault {
+            flags |= AmdIrte128Flags::SUPPRESS_IO_PAGE_FAULT;
+        }
+        if self.ga_log_interrupt {
+            flags |= AmdIrte128Flags::GA_LOG_INTERRUPT;
+        }
+        if self.is_running {
+            flags |= AmdIrte128Flags::IS_RUNNING;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl TryFrom<AmdIrte128Flags> for AmdGuestIrte {
+    type Error = RemappingError;
+
+    fn try_from(flags: AmdIrte128Flags) -> Result<Self, Self::Error> {
+        if !flags.is_guest_mode() {
+            return Err(RemappingError::NotRemappable);
+        }
+
+        Ok(AmdGuestIrte {
+            vector: flags.vector(),
+            ga_tag: flags.ga_tag(),
+            ga_root_pointer: flags.ga_root_pointer(),
+            destination: flags.destination(),
+            is_running: flags.contains(AmdIrte128Flags::IS_RUNNING),
+            ga_log_interrupt: flags.contains(AmdIrte128Flags::GA_LOG_INTERRUPT),
+            suppress_io_page_fault: flags.contains(AmdIrte128Flags::SUPPRESS_IO_PAGE_FAULT),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::TriggerMode;
+    use crate::remapping::{VtdDestinationFormat, VtdIrte};
+
+    #[test]
+    pub fn test_32bit_irte() {
+        let irte = AmdIrte::new(Vector(0x41), 0x12)
+            .with_delivery_mode(DeliveryMode::LowestPriority)
+            .with_destination_mode(DestinationMode::Logical);
+
+        let flags = irte.to_32bit_flags().unwrap();
+        assert_eq!(flags.bits(), 0x0041_1245);
+        assert_eq!(AmdIrte::from(flags), irte);
+        assert_eq!(AmdIrte::new(Vector(0x41), 0x100).to_32bit_flags(), Err(RemappingError::DestinationOutOfRange(0x100)));
+        assert_eq!(AmdIrte::new(Vector(0x41), 0).with_delivery_mode(DeliveryMode::Reserved1).to_32bit_flags(),
+            Err(RemappingError::ReservedDeliveryMode(DeliveryMode::Reserved1)));
+    }
+
+    #[test]
+    pub fn test_128bit_irte() {
+        let irte = AmdIrte::new(Vector(0x41), 0x1234_5678).with_request_eoi(true);
+
+        let flags = irte.to_128bit_flags().unwrap();
+        assert_eq!(flags.low(), 0x0000_0000_3456_7821);
+        assert_eq!(flags.high(), 0x1200_0000_0000_0041);
+        assert_eq!(AmdIrte::try_from(AmdIrte128Flags::from_halves(flags.low(), flags.high())), Ok(irte));
+        assert_eq!(AmdGuestIrte::try_from(flags), Err(RemappingError::NotRemappable));
+    }
+
+    #[test]
+    pub fn test_guest_irte() {
+        let irte = AmdGuestIrte::new(Vector(0x50), 0xdead_beef, 0x12_3456_7000).with_running(3);
+
+        let flags = irte.to_flags().unwrap();
+        assert!(flags.is_guest_mode());
+        assert_eq!(flags.low(), 0xdead_beef_0000_03c1);
+        assert_eq!(flags.high(), 0x0000_0012_3456_7050);
+        assert_eq!(AmdGuestIrte::try_from(flags), Ok(irte));
+        assert_eq!(AmdIrte::try_from(flags), Err(RemappingError::GuestMode));
+        assert_eq!(AmdGuestIrte::new(Vector(0x50), 0, 0x800).to_flags(), Err(RemappingError::MisalignedDescriptor(0x800)));
+    }
+
+    #[test]
+    pub fn test_from_redirection_entry() {
+        let entry = RedirectionEntry::new(Vector(0x41), Destination::Logical(0x03))
+            .with_delivery_mode(DeliveryMode::LowestPriority)
+            .with_trigger_mode(TriggerMode::Level);
+
+        let amd = AmdIrte::try_from(entry).unwrap();
+        assert_eq!(amd.destination_mode, DestinationMode::Logical);
+        assert_eq!(amd.to_32bit_flags().unwrap().bits(), 0x0041_0345);
+
+        let vtd = VtdIrte::try_from(entry).unwrap();
+        assert_eq!(vtd.trigger_mode, TriggerMode::Level);
+        assert_eq!(vtd.to_flags(VtdDestinationFormat::XApic).unwrap().low(), 0x0000_0300_0041_0035);
+
+        let invalid = entry.with_vector(Vector(0x0f));
+        assert_eq!(AmdIrte::try_from(invalid), Err(RemappingError::VectorOutOfRange(Vector(0x0f))));
+        assert_eq!(VtdIrte::try_from(invalid), Err(RemappingError::VectorOutOfRange(Vector(0x0f))));
+    }
+}
@@ -0,0 +1,29 @@
+pub mod amd;
+pub mod vtd;
+
+pub use amd::*;
+pub use vtd::*;
+
+use core::result::Result;
+use crate::io::{DeliveryError, DeliveryMode, RedirectionEntry, Vector};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RemappingError {
+    VectorOutOfRange(Vector),
+    ReservedDeliveryMode(DeliveryMode),
+    DestinationOutOfRange(u32),
+    HandleOutOfRange(u32),
+    MisalignedDescriptor(u64),
+    NotRemappable,
+    GuestMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InterruptHandle(pub u16);
+
+pub(crate) fn validate_delivery(vector: Vector, delivery_mode: DeliveryMode) -> Result<(), RemappingError> {
+    RedirectionEntry::check_delivery(vector, delivery_mode).map_err(|error| match error {
+        DeliveryError::ReservedDeliveryMode => RemappingError::ReservedDeliveryMode(delivery_mode),
+        DeliveryError::VectorOutOfRange => RemappingError::VectorOutOfRange(vector),
+    })
+}
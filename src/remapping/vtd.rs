@@ -0,0 +1,702 @@
+use core::result::Result;
+use core::convert::TryFrom;
+use crate::io::{
+    DeliveryMode, Destination, DestinationMode, Mask, Polarity, RedirectionEntry, RedirectionEntryFlags, TriggerMode,
+    Vector,
+};
+use crate::msi::MsiAddressFlags;
+use crate::remapping::{InterruptHandle, RemappingError, validate_delivery};
+
+bitflags! {
+    pub struct RemappableRedirectionEntryFlags: u64 {
+        const VECTOR           = 0x0000_0000_0000_00ff;
+        const RESERVED_LOW     = 0x0000_0000_0000_0700;
+        const HANDLE_15        = 0x0000_0000_0000_0800;
+        const DELIVERY_STATUS  = 0x0000_0000_0000_1000;
+        const POLARITY         = 0x0000_0000_0000_2000;
+        const REMOTE_IRR       = 0x0000_0000_0000_4000;
+        const TRIGGER_MODE     = 0x0000_0000_0000_8000;
+        const MASK             = 0x0000_0000_0001_0000;
+        const RESERVED         = 0x0000_ffff_fffe_0000;
+        const INTERRUPT_FORMAT = 0x0001_0000_0000_0000;
+        const HANDLE           = 0xfffe_0000_0000_0000;
+    }
+}
+
+impl RemappableRedirectionEntryFlags {
+    pub fn is_remappable(&self) -> bool {
+        self.contains(RemappableRedirectionEntryFlags::INTERRUPT_FORMAT)
+    }
+
+    pub fn handle(&self) -> InterruptHandle {
+        let low = (*self & RemappableRedirectionEntryFlags::HANDLE).bits() >> 49;
+        let high = (*self & RemappableRedirectionEntryFlags::HANDLE_15).bits() >> 11;
+        InterruptHandle(((high << 15) | low) as u16)
+    }
+
+    pub fn vector(&self) -> Vector {
+        Vector((*self & RemappableRedirectionEntryFlags::VECTOR).bits() as u32)
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        RedirectionEntryFlags::from(*self).polarity()
+    }
+
+    pub fn trigger_mode(&self) -> TriggerMode {
+        RedirectionEntryFlags::from(*self).trigger_mode()
+    }
+
+    pub fn mask(&self) -> Mask {
+        RedirectionEntryFlags::from(*self).mask()
+    }
+}
+
+impl From<InterruptHandle> for RemappableRedirectionEntryFlags {
+    fn from(handle: InterruptHandle) -> Self {
+        let handle = handle.0 as u64;
+        RemappableRedirectionEntryFlags::from_bits_truncate(((handle & 0x7fff) << 49) | ((handle >> 15) << 11))
+    }
+}
+
+impl From<RemappableRedirectionEntryFlags> for RedirectionEntryFlags {
+    fn from(flags: RemappableRedirectionEntryFlags) -> Self {
+        RedirectionEntryFlags::from_bits_truncate(flags.bits())
+    }
+}
+
+impl TryFrom<RedirectionEntryFlags> for RemappableRedirectionEntryFlags {
+    type Error = RemappingError;
+
+    fn try_from(flags: RedirectionEntryFlags) -> Result<Self, Self::Error> {
+        let flags = RemappableRedirectionEntryFlags::from_bits_truncate(flags.bits());
+        if flags.is_remappable() {
+            Ok(flags)
+        } else {
+            Err(RemappingError::NotRemappable)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RemappableRedirectionEntry {
+    pub handle: InterruptHandle,
+    pub vector: Vector,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+    pub mask: Mask,
+}
+
+impl RemappableRedirectionEntry {
+    pub fn new(handle: InterruptHandle, vector: Vector) -> Self {
+        RemappableRedirectionEntry {
+            handle,
+            vector,
+            polarity: Polarity::ActiveHigh,
+            trigger_mode: TriggerMode::Edge,
+            mask: Mask::NotMasked,
+        }
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn with_trigger_mode(mut self, trigger_mode: TriggerMode) -> Self {
+        self.trigger_mode = trigger_mode;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn to_flags(&self) -> RemappableRedirectionEntryFlags {
+        let flags = RedirectionEntryFlags::from(self.vector)
+            | RedirectionEntryFlags::from(self.polarity)
+            | RedirectionEntryFlags::from(self.trigger_mode)
+            | RedirectionEntryFlags::from(self.mask);
+
+        RemappableRedirectionEntryFlags::from_bits_truncate(flags.bits())
+            | RemappableRedirectionEntryFlags::from(self.handle)
+            | RemappableRedirectionEntryFlags::INTERRUPT_FORMAT
+    }
+}
+
+impl From<RemappableRedirectionEntryFlags> for RemappableRedirectionEntry {
+    fn from(flags: RemappableRedirectionEntryFlags) -> Self {
+        RemappableRedirectionEntry {
+            handle: flags.handle(),
+            vector: flags.vector(),
+            polarity: flags.polarity(),
+            trigger_mode: flags.trigger_mode(),
+            mask: flags.mask(),
+        }
+    }
+}
+
+bitflags! {
+    pub struct RemappableMsiAddressFlags: u32 {
+        const BASE             = 0xfff0_0000;
+        const HANDLE           = 0x000f_ffe0;
+        const INTERRUPT_FORMAT = 0x0000_0010;
+        const SUBHANDLE_VALID  = 0x0000_0008;
+        const HANDLE_15        = 0x0000_0004;
+        const RESERVED         = 0x0000_0003;
+    }
+}
+
+impl RemappableMsiAddressFlags {
+    pub fn new(handle: InterruptHandle, subhandle_valid: bool) -> Self {
+        let value = handle.0 as u32;
+        let mut flags = RemappableMsiAddressFlags::from_bits_truncate(
+            MsiAddressFlags::BASE_ADDRESS | ((value & 0x7fff) << 5) | ((value >> 15) << 2))
+            | RemappableMsiAddressFlags::INTERRUPT_FORMAT;
+
+        if subhandle_valid {
+            flags |= RemappableMsiAddressFlags::SUBHANDLE_VALID;
+        }
+
+        flags
+    }
+
+    pub fn is_remappable(&self) -> bool {
+        self.contains(RemappableMsiAddressFlags::INTERRUPT_FORMAT)
+    }
+
+    pub fn has_subhandle(&self) -> bool {
+        self.contains(RemappableMsiAddressFlags::SUBHANDLE_VALID)
+    }
+
+    pub fn handle(&self) -> InterruptHandle {
+        let low = (*self & RemappableMsiAddressFlags::HANDLE).bits() >> 5;
+        let high = (*self & RemappableMsiAddressFlags::HANDLE_15).bits() >> 2;
+        InterruptHandle(((high << 15) | low) as u16)
+    }
+
+    pub fn interrupt_index(&self, data: u32) -> u32 {
+        if self.has_subhandle() {
+            self.handle().0 as u32 + (data & 0xffff)
+        } else {
+            self.handle().0 as u32
+        }
+    }
+}
+
+impl TryFrom<MsiAddressFlags> for RemappableMsiAddressFlags {
+    type Error = RemappingError;
+
+    fn try_from(address: MsiAddressFlags) -> Result<Self, Self::Error> {
+        let address = RemappableMsiAddressFlags::from_bits_truncate(address.bits());
+        if address.is_remappable() {
+            Ok(address)
+        } else {
+            Err(RemappingError::NotRemappable)
+        }
+    }
+}
+
+impl From<RemappableMsiAddressFlags> for MsiAddressFlags {
+    fn from(address: RemappableMsiAddressFlags) -> Self {
+        MsiAddressFlags::from_bits_truncate(address.bits())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum SourceQualifier {
+    CompareAll = 0x0,
+    IgnoreFunction2,
+    IgnoreFunction21,
+    IgnoreFunction,
+}
+
+impl SourceQualifier {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_u128(self) -> u128 {
+        self.as_u8() as u128
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SourceValidation {
+    None,
+    RequesterId { source_id: u16, qualifier: SourceQualifier },
+    BusRange { start: u8, end: u8 },
+}
+
+impl SourceValidation {
+    pub fn from_flags(flags: u128) -> Self {
+        let source_id = (flags >> 64) as u16;
+
+        match (flags >> 80) as u8 & 0xf {
+            v if v >> 2 == 0x1 => {
+                let qualifier = match v & 0x3 {
+                    0x0 => SourceQualifier::CompareAll,
+                    0x1 => SourceQualifier::IgnoreFunction2,
+                    0x2 => SourceQualifier::IgnoreFunction21,
+                    _ => SourceQualifier::IgnoreFunction,
+                };
+                SourceValidation::RequesterId { source_id, qualifier }
+            }
+            v if v >> 2 == 0x2 => SourceValidation::BusRange { start: (source_id >> 8) as u8, end: source_id as u8 },
+            _ => SourceValidation::None,
+        }
+    }
+
+    pub fn as_u128(self) -> u128 {
+        match self {
+            SourceValidation::None => 0,
+            SourceValidation::RequesterId { source_id, qualifier } => {
+                (0x1 << 82) | (qualifier.as_u128() << 80) | ((source_id as u128) << 64)
+            }
+            SourceValidation::BusRange { start, end } => {
+                (0x2 << 82) | ((start as u128) << 72) | ((end as u128) << 64)
+            }
+        }
+    }
+}
+
+bitflags! {
+    pub struct VtdIrteFlags: u128 {
+        const PRESENT                  = 0x0000_0000_0000_0000_0000_0000_0000_0001;
+        const FAULT_PROCESSING_DISABLE = 0x0000_0000_0000_0000_0000_0000_0000_0002;
+        const DESTINATION_MODE         = 0x0000_0000_0000_0000_0000_0000_0000_0004;
+        const REDIRECTION_HINT         = 0x0000_0000_0000_0000_0000_0000_0000_0008;
+        const TRIGGER_MODE             = 0x0000_0000_0000_0000_0000_0000_0000_0010;
+        const DELIVERY_MODE            = 0x0000_0000_0000_0000_0000_0000_0000_00e0;
+        const AVAILABLE                = 0x0000_0000_0000_0000_0000_0000_0000_0f00;
+        const RESERVED_LOW             = 0x0000_0000_0000_0000_0000_0000_ff00_7000;
+        const IRTE_MODE                = 0x0000_0000_0000_0000_0000_0000_0000_8000;
+        const VECTOR                   = 0x0000_0000_0000_0000_0000_0000_00ff_0000;
+        const DESTINATION              = 0x0000_0000_0000_0000_ffff_ffff_0000_0000;
+        const SOURCE_ID                = 0x0000_0000_0000_ffff_0000_0000_0000_0000;
+        const SOURCE_QUALIFIER         = 0x0000_0000_0003_0000_0000_0000_0000_0000;
+        const SOURCE_VALIDATION_TYPE   = 0x0000_0000_000c_0000_0000_0000_0000_0000;
+        const RESERVED_HIGH            = 0xffff_ffff_fff0_0000_0000_0000_0000_0000;
+    }
+}
+
+impl VtdIrteFlags {
+    pub fn from_halves(low: u64, high: u64) -> Self {
+        VtdIrteFlags::from_bits_truncate(((high as u128) << 64) | low as u128)
+    }
+
+    pub fn low(&self) -> u64 {
+        self.bits() as u64
+    }
+
+    pub fn high(&self) -> u64 {
+        (self.bits() >> 64) as u64
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.contains(VtdIrteFlags::PRESENT)
+    }
+
+    pub fn is_posted(&self) -> bool {
+        self.contains(VtdIrteFlags::IRTE_MODE)
+    }
+
+    pub fn destination_mode(&self) -> DestinationMode {
+        if self.contains(VtdIrteFlags::DESTINATION_MODE) {
+            DestinationMode::Logical
+        } else {
+            DestinationMode::Physical
+        }
+    }
+
+    pub fn trigger_mode(&self) -> TriggerMode {
+        if self.contains(VtdIrteFlags::TRIGGER_MODE) {
+            TriggerMode::Level
+        } else {
+            TriggerMode::Edge
+        }
+    }
+
+    pub fn delivery_mode(&self) -> DeliveryMode {
+        DeliveryMode::try_from(((*self & VtdIrteFlags::DELIVERY_MODE).bits() >> 5) as u8).unwrap()
+    }
+
+    pub fn vector(&self) -> Vector {
+        Vector(((*self & VtdIrteFlags::VECTOR).bits() >> 16) as u32)
+    }
+
+    pub fn destination(&self, format: VtdDestinationFormat) -> u32 {
+        let destination = ((*self & VtdIrteFlags::DESTINATION).bits() >> 32) as u32;
+
+        match format {
+            VtdDestinationFormat::XApic => (destination >> 8) & 0xff,
+            VtdDestinationFormat::X2Apic => destination,
+        }
+    }
+
+    pub fn source_validation(&self) -> SourceValidation {
+        SourceValidation::from_flags(self.bits())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VtdDestinationFormat {
+    XApic,
+    X2Apic,
+}
+
+impl VtdDestinationFormat {
+    pub fn max_id(self) -> u32 {
+        match self {
+            VtdDestinationFormat::XApic => 0xff,
+            VtdDestinationFormat::X2Apic => 0xffff_ffff,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VtdIrte {
+    pub vector: Vector,
+    pub delivery_mode: DeliveryMode,
+    pub destination_mode: DestinationMode,
+    pub redirection_hint: bool,
+    pub trigger_mode: TriggerMode,
+    pub destination: u32,
+    pub fault_processing_disable: bool,
+    pub source_validation: SourceValidation,
+}
+
+impl VtdIrte {
+    pub fn new(vector: Vector, destination: u32) -> Self {
+        VtdIrte {
+            vector,
+            delivery_mode: DeliveryMode::Fixed,
+            destination_mode: DestinationMode::Physical,
+            redirection_hint: false,
+            trigger_mode: TriggerMode::Edge,
+            destination,
+            fault_processing_disable: false,
+            source_validation: SourceValidation::None,
+        }
+    }
+
+    pub fn from_flags(flags: VtdIrteFlags, format: VtdDestinationFormat) -> Self {
+        VtdIrte {
+            vector: flags.vector(),
+            delivery_mode: flags.delivery_mode(),
+            destination_mode: flags.destination_mode(),
+            redirection_hint: flags.contains(VtdIrteFlags::REDIRECTION_HINT),
+            trigger_mode: flags.trigger_mode(),
+            destination: flags.destination(format),
+            fault_processing_disable: flags.contains(VtdIrteFlags::FAULT_PROCESSING_DISABLE),
+            source_validation: flags.source_validation(),
+        }
+    }
+
+    pub fn with_delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
+        self.delivery_mode = delivery_mode;
+        self
+    }
+
+    pub fn with_destination_mode(mut self, destination_mode: DestinationMode) -> Self {
+        self.destination_mode = destination_mode;
+        self
+    }
+
+    pub fn with_redirection_hint(mut self, redirection_hint: bool) -> Self {
+        self.redirection_hint = redirection_hint;
+        self
+    }
+
+    pub fn with_trigger_mode(mut self, trigger_mode: TriggerMode) -> Self {
+        self.trigger_mode = trigger_mode;
+        self
+    }
+
+    pub fn with_fault_processing_disable(mut self, fault_processing_disable: bool) -> Self {
+        self.fault_processing_disable = fault_processing_disable;
+        self
+    }
+
+    pub fn with_source_validation(mut self, source_validation: SourceValidation) -> Self {
+        self.source_validation = source_validation;
+        self
+    }
+
+    pub fn validate(&self, format: VtdDestinationFormat) -> Result<(), RemappingError> {
+        validate_delivery(self.vector, self.delivery_mode)?;
+
+        if self.destination > format.max_id() {
+            return Err(RemappingError::DestinationOutOfRange(self.destination));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_flags(&self, format: VtdDestinationFormat) -> Result<VtdIrteFlags, RemappingError> {
+        self.validate(format)?;
+
+        let destination = match format {
+            VtdDestinationFormat::XApic => self.destination << 8,
+            VtdDestinationFormat::X2Apic => self.destination,
+        };
+
+        let mut flags = VtdIrteFlags::from_bits_truncate(
+            ((self.vector.0 as u128 & 0xff) << 16)
+                | (self.delivery_mode.as_u8() as u128) << 5
+                | (destination as u128) << 32
+                | self.source_validation.as_u128())
+            | VtdIrteFlags::PRESENT;
+
+        if self.destination_mode == DestinationMode::Logical {
+            flags |= VtdIrteFlags::DESTINATION_MODE;
+        }
+        if self.redirection_hint {
+            flags |= VtdIrteFlags::REDIRECTION_HINT;
+        }
+        if self.trigger_mode == TriggerMode::Level {
+            flags |= VtdIrteFlags::TRIGGER_MODE;
+        }
+        if self.fault_processing_disable {
+            flags |= VtdIrteFlags::FAULT_PROCESSING_DISABLE;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl TryFrom<RedirectionEntry> for VtdIrte {
+    type Error = RemappingError;
+
+    fn try_from(entry: RedirectionEntry) -> Result<Self, Self::Error> {
+        let destination = match entry.destination {
+            Destination::Physical(id) | Destination::Logical(id) => id as u32,
+        };
+
+        let irte = VtdIrte::new(entry.vector, destination)
+            .with_delivery_mode(entry.delivery_mode)
+            .with_destination_mode(entry.destination_mode())
+            .with_trigger_mode(entry.trigger_mode);
+
+        irte.validate(VtdDestinationFormat::XApic)?;
+        Ok(irte)
+    }
+}
+
+bitflags! {
+    pub struct VtdPostedIrteFlags: u128 {
+        const PRESENT                  = 0x0000_0000_0000_0000_0000_0000_0000_0001;
+        const FAULT_PROCESSING_DISABLE = 0x0000_0000_0000_0000_0000_0000_0000_0002;
+        const RESERVED_LOW             = 0x0000_0000_0000_0000_0000_003f_ff00_30fc;
+        const AVAILABLE                = 0x0000_0000_0000_0000_0000_0000_0000_0f00;
+        const URGENT                   = 0x0000_0000_0000_0000_0000_0000_0000_4000;
+        const IRTE_MODE                = 0x0000_0000_0000_0000_0000_0000_0000_8000;
+        const VECTOR                   = 0x0000_0000_0000_0000_0000_0000_00ff_0000;
+        const DESCRIPTOR_LOW           = 0x0000_0000_0000_0000_ffff_ffc0_0000_0000;
+        const SOURCE_ID                = 0x0000_0000_0000_ffff_0000_0000_0000_0000;
+        const SOURCE_QUALIFIER         = 0x0000_0000_0003_0000_0000_0000_0000_0000;
+        const SOURCE_VALIDATION_TYPE   = 0x0000_0000_000c_0000_0000_0000_0000_0000;
+        const RESERVED_HIGH            = 0x0000_0000_fff0_0000_0000_0000_0000_0000;
+        const DESCRIPTOR_HIGH          = 0xffff_ffff_0000_0000_0000_0000_0000_0000;
+    }
+}
+
+impl VtdPostedIrteFlags {
+    pub fn from_halves(low: u64, high: u64) -> Self {
+        VtdPostedIrteFlags::from_bits_truncate(((high as u128) << 64) | low as u128)
+    }
+
+    pub fn low(&self) -> u64 {
+        self.bits() as u64
+    }
+
+    pub fn high(&self) -> u64 {
+        (self.bits() >> 64) as u64
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.contains(VtdPostedIrteFlags::PRESENT)
+    }
+
+    pub fn is_urgent(&self) -> bool {
+        self.contains(VtdPostedIrteFlags::URGENT)
+    }
+
+    pub fn vector(&self) -> Vector {
+        Vector(((*self & VtdPostedIrteFlags::VECTOR).bits() >> 16) as u32)
+    }
+
+    pub fn descriptor_address(&self) -> u64 {
+        let low = ((*self & VtdPostedIrteFlags::DESCRIPTOR_LOW).bits() >> 32) as u64;
+        let high = ((*self & VtdPostedIrteFlags::DESCRIPTOR_HIGH).bits() >> 96) as u64;
+        (high << 32) | low
+    }
+
+    pub fn source_validation(&self) -> SourceValidation {
+        SourceValidation::from_flags(self.bits())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VtdPostedIrte {
+    pub vector: Vector,
+    pub urgent: bool,
+    pub descriptor_address: u64,
+    pub fault_processing_disable: bool,
+    pub source_validation: SourceValidation,
+}
+
+impl VtdPostedIrte {
+    pub const DESCRIPTOR_ALIGNMENT: u64 = 64;
+
+    pub fn new(vector: Vector, descriptor_address: u64) -> Self {
+        VtdPostedIrte {
+            vector,
+            urgent: false,
+            descriptor_address,
+            fault_processing_disable: false,
+            source_validation: SourceValidation::None,
+        }
+    }
+
+    pub fn with_urgent(mut self, urgent: bool) -> Self {
+        self.urgent = urgent;
+        self
+    }
+
+    pub fn with_fault_processing_disable(mut self, fault_processing_disable: bool) -> Self {
+        self.fault_processing_disable = fault_processing_disable;
+        self
+    }
+
+    pub fn with_source_validation(mut self, source_validation: SourceValidation) -> Self {
+        self.source_validation = source_validation;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), RemappingError> {
+        validate_delivery(self.vector, DeliveryMode::Fixed)?;
+
+        if self.descriptor_address & (Self::DESCRIPTOR_ALIGNMENT - 1) != 0 {
+            return Err(RemappingError::MisalignedDescriptor(self.descriptor_address));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_flags(&self) -> Result<VtdPostedIrteFlags, RemappingError> {
+        self.validate()?;
+
+        let mut flags = VtdPostedIrteFlags::from_bits_truncate(
+            ((self.vector.0 as u128 & 0xff) << 16)
+                | ((self.descriptor_address as u128 & 0xffff_ffc0) << 32)
+                | ((self.descriptor_address as u128 >> 32) << 96)
+                | self.source_validation.as_u128())
+            | VtdPostedIrteFlags::PRESENT
+            | VtdPostedIrteFlags::IRTE_MODE;
+
+        if self.urgent {
+            flags |= VtdPostedIrteFlags::URGENT;
+        }
+        if self.fault_processing_disable {
+            flags |= VtdPostedIrteFlags::FAULT_PROCESSING_DISABLE;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl From<VtdPostedIrteFlags> for VtdPostedIrte {
+    fn from(flags: VtdPostedIrteFlags) -> Self {
+        VtdPostedIrte {
+            vector: flags.vector(),
+            urgent: flags.is_urgent(),
+            descriptor_address: flags.descriptor_address(),
+            fault_processing_disable: flags.contains(VtdPostedIrteFlags::FAULT_PROCESSING_DISABLE),
+            source_validation: flags.source_validation(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msi::MsiMessage;
+
+    #[test]
+    pub fn test_remappable_redirection_entry() {
+        let entry = RemappableRedirectionEntry::new(InterruptHandle(0x8005), Vector(0x30))
+            .with_trigger_mode(TriggerMode::Level)
+            .with_polarity(Polarity::ActiveLow);
+
+        let flags = entry.to_flags();
+        assert_eq!(flags.bits(), 0x000b_0000_0000_a830);
+        assert_eq!(RemappableRedirectionEntry::from(flags), entry);
+
+        let compatibility = RedirectionEntryFlags::from(flags);
+        assert_eq!(RemappableRedirectionEntryFlags::try_from(compatibility), Ok(flags));
+        assert_eq!(RemappableRedirectionEntryFlags::try_from(RedirectionEntryFlags::empty()), Err(RemappingError::NotRemappable));
+    }
+
+    #[test]
+    pub fn test_remappable_msi_address() {
+        let address = RemappableMsiAddressFlags::new(InterruptHandle(0x8012), true);
+
+        assert_eq!(address.bits(), 0xfee0_025c);
+        assert_eq!(address.handle(), InterruptHandle(0x8012));
+        assert_eq!(address.interrupt_index(3), 0x8015);
+
+        let compatibility = MsiMessage::new(Vector(0x41), 0).address().unwrap();
+        assert_eq!(RemappableMsiAddressFlags::try_from(compatibility), Err(RemappingError::NotRemappable));
+        assert_eq!(RemappableMsiAddressFlags::try_from(MsiAddressFlags::from(address)), Ok(address));
+    }
+
+    #[test]
+    pub fn test_remapped_irte() {
+        let irte = VtdIrte::new(Vector(0x41), 0x12)
+            .with_destination_mode(DestinationMode::Logical)
+            .with_trigger_mode(TriggerMode::Level)
+            .with_source_validation(SourceValidation::RequesterId { source_id: 0x00f8, qualifier: SourceQualifier::CompareAll });
+
+        let flags = irte.to_flags(VtdDestinationFormat::XApic).unwrap();
+        assert_eq!(flags.low(), 0x0000_1200_0041_0015);
+        assert_eq!(flags.high(), 0x0000_0000_0004_00f8);
+        assert_eq!(VtdIrte::from_flags(VtdIrteFlags::from_halves(flags.low(), flags.high()), VtdDestinationFormat::XApic), irte);
+
+        let x2apic = VtdIrte::new(Vector(0x41), 0x1_0000)
+            .with_source_validation(SourceValidation::BusRange { start: 0x02, end: 0x04 });
+        let flags = x2apic.to_flags(VtdDestinationFormat::X2Apic).unwrap();
+        assert_eq!(flags.destination(VtdDestinationFormat::X2Apic), 0x1_0000);
+        assert_eq!(VtdIrte::from_flags(flags, VtdDestinationFormat::X2Apic), x2apic);
+        assert_eq!(x2apic.to_flags(VtdDestinationFormat::XApic), Err(RemappingError::DestinationOutOfRange(0x1_0000)));
+        assert_eq!(VtdIrte::new(Vector(0x0f), 0).to_flags(VtdDestinationFormat::XApic), Err(RemappingError::VectorOutOfRange(Vector(0x0f))));
+    }
+
+    #[test]
+    pub fn test_irte_from_redirection_entry() {
+        let entry = RedirectionEntry::new(Vector(0x41), Destination::Logical(0x12))
+            .with_trigger_mode(TriggerMode::Level);
+
+        let irte = VtdIrte::try_from(entry).unwrap();
+        assert_eq!(irte, VtdIrte::new(Vector(0x41), 0x12)
+            .with_destination_mode(DestinationMode::Logical)
+            .with_trigger_mode(TriggerMode::Level));
+
+        let reserved = entry.with_delivery_mode(DeliveryMode::Reserved0);
+        assert_eq!(VtdIrte::try_from(reserved), Err(RemappingError::ReservedDeliveryMode(DeliveryMode::Reserved0)));
+        assert_eq!(VtdIrte::try_from(entry.with_vector(Vector(0x0f))), Err(RemappingError::VectorOutOfRange(Vector(0x0f))));
+    }
+
+    #[test]
+    pub fn test_posted_irte() {
+        let irte = VtdPostedIrte::new(Vector(0xe0), 0x1_2345_6780).with_urgent(true);
+
+        let flags = irte.to_flags().unwrap();
+        assert!(flags.is_present());
+        assert_eq!(flags.low(), 0x2345_6780_00e0_c001);
+        assert_eq!(flags.high(), 0x0000_0001_0000_0000);
+        assert_eq!(VtdPostedIrte::from(flags), irte);
+        assert_eq!(VtdPostedIrte::new(Vector(0xe0), 0x1020).to_flags(), Err(RemappingError::MisalignedDescriptor(0x1020)));
+    }
+}